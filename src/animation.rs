@@ -0,0 +1,111 @@
+use serde::{Deserialize, Serialize};
+
+use crate::field::Field;
+use crate::game::GameEvent;
+use crate::point::Point;
+use crate::screen::{write_points, Screen};
+
+const FLASH_PERIOD : u64 = 60; //ms between the flashing rows switching on and off
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct AnimationSettings{
+    pub(crate) enabled : bool,
+    pub(crate) line_clear_ms : u64, //the game holds for this long before full rows are removed
+    pub(crate) lock_flash_ms : u64,
+    pub(crate) top_out_ms : u64,
+}
+
+impl Default for AnimationSettings {
+    fn default() -> Self {
+        AnimationSettings{enabled: true, line_clear_ms: 300, lock_flash_ms: 120, top_out_ms: 800}
+    }
+}
+
+pub struct Animations{
+    pub(crate) settings : AnimationSettings,
+    cleared_rows : Vec<i32>,
+    clear_started : u64,
+    locked : Vec<Point>,
+    lock_started : u64,
+    top_out_started : Option<u64>,
+}
+
+pub fn create_animations(settings : AnimationSettings) -> Animations{
+    Animations{settings, cleared_rows: vec![], clear_started: 0, locked: vec![], lock_started: 0, top_out_started: None}
+}
+
+pub fn line_clear_delay(settings : &AnimationSettings) -> u64{
+    if settings.enabled { settings.line_clear_ms } else { 0 }
+}
+
+pub fn start_animation(animations : &mut Animations, event : &GameEvent, now : u64){
+    if !animations.settings.enabled {
+        return;
+    }
+
+    match event {
+        GameEvent::Landed { vertices } => {
+            animations.locked = vertices.clone();
+            animations.lock_started = now;
+        },
+
+        GameEvent::RowsCleared { rows } => {
+            animations.cleared_rows = rows.clone();
+            animations.clear_started = now;
+        },
+
+        GameEvent::ToppedOut => animations.top_out_started = Some(now),
+
+        GameEvent::Spawned | GameEvent::GoalReached => {}
+    }
+}
+
+fn is_clearing(animations : &Animations, now : u64) -> bool{
+    !animations.cleared_rows.is_empty() && now < animations.clear_started + animations.settings.line_clear_ms
+}
+
+fn is_lock_flashing(animations : &Animations, now : u64) -> bool{
+    !animations.locked.is_empty() && now < animations.lock_started + animations.settings.lock_flash_ms
+}
+
+pub fn is_topping_out(animations : &Animations, now : u64) -> bool{
+    match animations.top_out_started {
+        Some(started) => now < started + animations.settings.top_out_ms,
+        None => false
+    }
+}
+
+pub fn is_animating(animations : &Animations, now : u64) -> bool{
+    is_clearing(animations, now) || is_lock_flashing(animations, now) || is_topping_out(animations, now)
+}
+
+//draws the running animations over an already printed field
+pub fn print_animations(animations : &Animations, field : &Field, screen : &Screen, now : u64){
+    if is_lock_flashing(animations, now) {
+        write_points(screen, &animations.locked, "#", screen.theme.hud);
+    }
+
+    if is_clearing(animations, now) {
+        let flash_on = ((now - animations.clear_started) / FLASH_PERIOD).is_multiple_of(2);
+        let symbol = if flash_on { "=" } else { " " };
+
+        for row in animations.cleared_rows.iter() {
+            write_points(screen, &row_points(field, *row), symbol, screen.theme.hud);
+        }
+    }
+
+    if let Some(started) = animations.top_out_started {
+        //fills the board row by row, starting from the bottom
+        let elapsed = now.saturating_sub(started).min(animations.settings.top_out_ms.max(1));
+        let filled_rows = (elapsed * field.height as u64 / animations.settings.top_out_ms.max(1)) as i32;
+
+        for row in (field.height as i32 - filled_rows)..(field.height as i32) {
+            write_points(screen, &row_points(field, row), "#", screen.theme.fallback);
+        }
+    }
+}
+
+fn row_points(field : &Field, row : i32) -> Vec<Point>{
+    (1..(field.width as i32 - 1)).map(|x| Point{x, y: row}).collect()
+}
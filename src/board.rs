@@ -0,0 +1,186 @@
+use crate::field::{create_field, place_cells, Field};
+use crate::game::{Game, Randomizer, State};
+use crate::point::Point;
+
+pub const EMPTY_CELL : char = '.';
+
+//a field in the text board format, with the pieces around it:
+//
+//  active: T
+//  hold: I
+//  queue: OSZ
+//  ..........
+//  GGGGGGGGG.
+//
+//one line per row from the top, `.` for an empty cell and the letter of the piece for a taken one;
+//the header lines are optional, lines starting with `#` are comments
+pub struct Board{
+    pub(crate) field : Field,
+    pub(crate) active : Option<char>,
+    pub(crate) hold : Option<char>,
+    pub(crate) queue : Vec<char>, //next piece first
+}
+
+//the cells of the field row by row from the top, without the side borders; cells above the top row are left out
+pub fn field_grid(field : &Field) -> Vec<Vec<Option<char>>>{
+    let mut grid = vec![vec![None; field.width as usize - 2]; field.height as usize];
+
+    for brick in field.bricks.iter() {
+        for vertex in brick.vertices.iter() {
+            if vertex.y >= 0 && vertex.y < field.height as i32 && vertex.x >= 1 && vertex.x <= field.width as i32 - 2 {
+                grid[vertex.y as usize][vertex.x as usize - 1] = Some(brick.kind);
+            }
+        }
+    }
+
+    return grid;
+}
+
+fn parse_piece_letter(key : &str, value : &str) -> Result<char, String>{
+    let mut letters = value.chars();
+
+    match (letters.next(), letters.next()) {
+        (Some(letter), None) if letter != EMPTY_CELL => Ok(letter),
+        _ => Err(format!("{}: expected a single piece letter, got '{}'", key, value))
+    }
+}
+
+pub fn parse_board(text : &str) -> Result<Board, String>{
+    let mut active = None;
+    let mut hold = None;
+    let mut queue : Vec<char> = vec![];
+    let mut rows : Vec<&str> = vec![];
+
+    for line in text.lines().map(|line| line.trim()).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+        let header = line.split_once(':').filter(|_| rows.is_empty()); //the header comes before the first row
+
+        match header.map(|(key, value)| (key.trim(), value.trim())) {
+            Some(("active", value)) => active = Some(parse_piece_letter("active", value)?),
+            Some(("hold", value)) => hold = Some(parse_piece_letter("hold", value)?),
+            Some(("queue", value)) => queue = value.chars().filter(|letter| !letter.is_whitespace()).collect(),
+            Some((key, _)) => return Err(format!("unknown header '{}', expected active, hold or queue", key)),
+            None => rows.push(line),
+        }
+    }
+
+    let width = rows.first().map_or(0, |row| row.chars().count());
+
+    if !(4..=40).contains(&width) {
+        return Err(format!("the rows must be 4 to 40 cells wide, got {}", width));
+    }
+
+    if rows.len() > 40 {
+        return Err(format!("at most 40 rows, got {}", rows.len()));
+    }
+
+    let mut cells : Vec<(Point, char)> = vec![];
+
+    for (y, row) in rows.iter().enumerate() {
+        if row.chars().count() != width {
+            return Err(format!("row {} is {} cells wide, the first one {}", y + 1, row.chars().count(), width));
+        }
+
+        if row.chars().any(|cell| cell.is_whitespace() || cell == ':') {
+            return Err(format!("row {}: cells are `.` or piece letters", y + 1));
+        }
+
+        for (x, kind) in row.chars().enumerate().filter(|(_, kind)| *kind != EMPTY_CELL) {
+            cells.push((Point{x: x as i32 + 1, y: y as i32}, kind)); //right of the left border
+        }
+    }
+
+    let mut field = create_field(width as u16 + 2, rows.len() as u16);
+    place_cells(&mut field, &cells, 0);
+
+    return Ok(Board{field, active, hold, queue});
+}
+
+pub fn render_board(board : &Board) -> String{
+    let mut text = String::new();
+
+    if let Some(active) = board.active {
+        text.push_str(&format!("active: {}\n", active));
+    }
+
+    if let Some(hold) = board.hold {
+        text.push_str(&format!("hold: {}\n", hold));
+    }
+
+    if !board.queue.is_empty() {
+        text.push_str(&format!("queue: {}\n", board.queue.iter().collect::<String>()));
+    }
+
+    for row in field_grid(&board.field) {
+        text.extend(row.iter().map(|cell| cell.unwrap_or(EMPTY_CELL)));
+        text.push('\n');
+    }
+
+    return text;
+}
+
+//the field of the game with its active piece, hold and the pieces known to come next
+pub fn game_board(game : &Game) -> Board{
+    let queue : Vec<usize> = match (&game.sequence, game.randomizer) {
+        (Some(sequence), _) => sequence.iter().rev().copied().collect(),
+        (None, Randomizer::Bag) => game.bag.iter().rev().copied().collect(),
+        (None, Randomizer::Random) => vec![], //drawn only when needed
+    };
+
+    Board{
+        field: game.field.clone(),
+        active: Some(game.brick.kind).filter(|_| game.state == State::Descend),
+        hold: game.held_brick.as_ref().map(|brick| brick.kind),
+        queue: queue.iter().map(|idx| game.pieces.bricks[*idx].kind).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{add_garbage, create_game, hard_drop, move_brick_to_column, update_game};
+
+    fn sorted_cells(field : &Field) -> Vec<(i32, i32, char)> {
+        let mut cells : Vec<(i32, i32, char)> = field.bricks.iter()
+            .flat_map(|brick| brick.vertices.iter().map(|vertex| (vertex.x, vertex.y, brick.kind)))
+            .collect();
+        cells.sort();
+
+        return cells;
+    }
+
+    #[test]
+    fn a_board_comes_back_unchanged_from_parse_and_render() {
+        let text = "active: T\nhold: I\nqueue: OSZ\n..........\n....LL....\nG..ZZLJJJ.\nGGGGGGGGG.\n";
+        let board = parse_board(text).unwrap();
+
+        assert_eq!(board.field.width, 12);
+        assert_eq!(board.field.height, 4);
+        assert_eq!(render_board(&board), text);
+    }
+
+    #[test]
+    fn a_played_field_comes_back_unchanged_from_render_and_parse() {
+        let mut game = create_game(12, 12, 7);
+        game.messiness = 0.5;
+        update_game(&mut game, 0);
+        add_garbage(&mut game, 3);
+
+        for column in [2, 5, 8, 3] {
+            move_brick_to_column(&mut game, column);
+            hard_drop(&mut game);
+            let now = game.time + 1;
+            update_game(&mut game, now);
+        }
+
+        let board = parse_board(&render_board(&game_board(&game))).unwrap();
+
+        assert_eq!(sorted_cells(&board.field), sorted_cells(&game.field));
+        assert_eq!(board.active, Some(game.brick.kind));
+    }
+
+    #[test]
+    fn ragged_rows_and_unknown_headers_are_rejected() {
+        assert!(parse_board("....\n...\n").is_err_and(|err| err.contains("row 2")));
+        assert!(parse_board("next: T\n....\n").is_err_and(|err| err.contains("unknown header")));
+    }
+}
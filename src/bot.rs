@@ -0,0 +1,103 @@
+use std::sync::mpsc;
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::Duration;
+
+use crate::field::Field;
+use crate::game::{apply_action, update_game, Game, Outcome};
+use crate::input::{pressed, released, Action};
+use crate::input_source::{send_input, InputSender};
+
+//scores a field after a placement, higher is better (weights of the well known Yiyuan Lee heuristic)
+fn evaluate_field(field : &Field, cleared_rows : u32) -> f64{
+    let columns = (1..(field.width as i32 - 1)).collect::<Vec<i32>>();
+    let mut heights : Vec<i32> = vec![0; columns.len()];
+    let mut holes = 0;
+
+    for (idx, col) in columns.iter().enumerate() {
+        let mut column_cells : Vec<i32> = field.bricks.iter()
+            .flat_map(|brick| brick.vertices.iter())
+            .filter(|vertex| vertex.x == *col)
+            .map(|vertex| vertex.y)
+            .collect();
+        column_cells.sort();
+
+        if let Some(top) = column_cells.first() {
+            heights[idx] = field.height as i32 - top;
+            holes += (field.height as i32 - top) - column_cells.len() as i32;
+        }
+    }
+
+    let aggregate_height : i32 = heights.iter().sum();
+    let bumpiness : i32 = heights.windows(2).map(|pair| (pair[0] - pair[1]).abs()).sum();
+
+    return -0.51 * aggregate_height as f64 + 0.76 * cleared_rows as f64 - 0.36 * holes as f64 - 0.18 * bumpiness as f64;
+}
+
+fn simulate(game : &Game, actions : &[Action]) -> Option<(Field, u32)>{
+    let mut sim = game.clone();
+    sim.line_clear_delay = 0;
+    sim.events.clear();
+
+    for action in actions.iter() {
+        if !apply_action(&mut sim, *action) {
+            return None; //the placement cannot be reached
+        }
+    }
+
+    //lands the brick and resolves the clears, the next brick only becomes active and is not on the field
+    let now = sim.time;
+    update_game(&mut sim, now);
+
+    if sim.outcome == Outcome::Lost {
+        return None;
+    }
+
+    return Some((sim.field, sim.lines - game.lines));
+}
+
+//finds the best rotation and column for the active brick and returns the actions which get it there
+pub fn plan_placement(game : &Game) -> Vec<Action>{
+    let mut best : Option<(f64, Vec<Action>)> = None;
+
+    for rotation in [None, Some(Action::RotateCw), Some(Action::Rotate180), Some(Action::RotateCcw)] {
+        for offset in -(game.field.width as i32)..(game.field.width as i32) {
+            let mut actions : Vec<Action> = rotation.into_iter().collect();
+            let step = if offset < 0 { Action::MoveLeft } else { Action::MoveRight };
+            actions.extend(std::iter::repeat_n(step, offset.unsigned_abs() as usize));
+            actions.push(Action::HardDrop);
+
+            if let Some((field, cleared_rows)) = simulate(game, &actions) {
+                let value = evaluate_field(&field, cleared_rows);
+
+                if best.as_ref().is_none_or(|(best_value, _)| value > *best_value) {
+                    best = Some((value, actions));
+                }
+            }
+        }
+    }
+
+    return match best {
+        Some((_, actions)) => actions,
+        None => vec![Action::HardDrop]
+    };
+}
+
+//runs the bot on its own thread: it gets a copy of the game whenever a brick spawns and answers with key presses
+pub fn spawn_bot(step_delay : Duration, input : InputSender) -> Sender<Game>{
+    let (game_tx, game_rx) = mpsc::channel::<Game>();
+
+    thread::spawn(move || {
+        while let Ok(game) = game_rx.recv() {
+            for action in plan_placement(&game).iter() {
+                thread::sleep(step_delay);
+
+                if !send_input(&input, pressed(*action, 0)) || !send_input(&input, released(*action, 0)) {
+                    return;
+                }
+            }
+        }
+    });
+
+    return game_tx;
+}
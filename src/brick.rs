@@ -1,123 +1,74 @@
-use std::io;
-use crossterm::{ExecutableCommand, style,};
-use crossterm::style::Color;
-use rand::distributions::{Distribution, Standard};
-use rand::{random, Rng};
+use serde::{Deserialize, Serialize};
 
 use crate::brick::BrickOrientation::{Horizontal, Vertical};
 use crate::point::{Point};
-use crate::utility::{clear_console, get_screen_translated_vertices, move_to_and_write};
+use crate::screen::{Screen, write_points};
+use crate::theme::piece_color;
+use crate::utility::get_screen_translated_vertices;
 
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum BrickOrientation { //every rotation happens clockwise
     Horizontal,
     Vertical,
 }
-#[derive(Clone)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct Brick{
     pub(crate) vertices : Vec<Point>,  //points are bottom-left corners of tile that builds the brick
     pub(crate) width_horizontal : u16,
     pub(crate) height_horizontal: u16,
     pub(crate) orientation: BrickOrientation,
-    pub(crate) color : Color,
+    pub(crate) kind : char, //letter of the shape, the theme maps it to a color
+    pub(crate) scale : u16, //field cells per side of a vertex, 2 in big mode
+    pub(crate) centre : Point, //of the rotation, doubled so that a corner between cells is a point as well
+    pub(crate) spawn_offset : Point, //columns to the right and rows up from where bricks spawn
+    pub(crate) kicks : Option<Vec<Point>>, //replaces the kicks of the rotation system
 }
 
-#[derive(Clone)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct DeadBrick {
     pub(crate) vertices : Vec<Point>, //already translated to screen coordinates
-    pub(crate) color : Color,
+    pub(crate) kind : char,
+    #[serde(default)]
+    pub(crate) locked_at : u64, //game time all its cells landed, the latest one for pieces stuck together
 }
-pub(crate) enum BrickShapes {
-    S,
-    L,
-    Box,
-    I,
-    Castle,
-}
-
-impl Distribution<BrickShapes> for Standard {
-    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> BrickShapes {
-        match rng.gen_range(0..5) {
-            0 => BrickShapes::I,
-            1 => BrickShapes::S,
-            2 => BrickShapes::L,
-            3 => BrickShapes::Castle,
-            _ => BrickShapes::Box,
-        }
-    }
-}
-
-pub fn generate_brick() -> Brick{
-    let version = random::<BrickShapes>();
-    let vertices : Vec<Point> =
-        match version{
-            BrickShapes::I => vec!{Point{x : 0, y : 0},
-                                   Point{x : 0, y : 1},
-                                   Point{x : 0, y : 2},
-            },
-
-            BrickShapes::S => vec!{Point{x : 0, y : 0},
-                                   Point{x : 1, y : 0},
-                                   Point{x : 1, y : 1},
-                                   Point{x : 2, y : 1},
-            },
-
-            BrickShapes::Box => vec!{Point{x : 0, y : 0},
-                                     Point{x : 0, y : 1},
-                                     Point{x : 1, y : 1},
-                                     Point{x : 1, y : 0},
-            },
-
-            BrickShapes::L => vec!{Point{x : 0, y : 0},
-                                   Point{x : 1, y : 0},
-                                   Point{x : 0, y : 1},
-            },
-
-            BrickShapes::Castle => vec!{Point{x : 0, y : 0},
-                                        Point{x : 1, y : 1},
-                                        Point{x : 1, y : 0},
-                                        Point{x : 2, y : 0},
-            },
-        };
-
-    return build_brick(vertices);
-}
-
-pub fn build_brick(vertices : Vec<Point>) -> Brick {
+pub fn build_brick(vertices : Vec<Point>, kind : char) -> Brick {
+    let centre = derive_centre(&vertices);
     let width = calculate_width(&vertices);
     let height = calculate_height(&vertices);
 
-    let color =
-    match rand::thread_rng().gen_range(0..5) {
-        0 => Color::Red,
-        1 => Color::Green,
-        2 => Color::Blue,
-        3 => Color::Grey,
-        _ => Color::DarkYellow,
-    };
-
-    return Brick{vertices, width_horizontal: width, height_horizontal: height, orientation: Horizontal, color};
-}
-
-pub fn get_height(brick : &Brick) -> u16{
-    if brick.orientation == Horizontal{
-        brick.height_horizontal
-    }
-    else{
-       brick.width_horizontal
-    }
+    return Brick{vertices, width_horizontal: width, height_horizontal: height, orientation: Horizontal, kind, scale: 1, centre, spawn_offset: Point{x: 0, y: 0}, kicks: None};
 }
 
+//in field cells
 pub fn get_width(brick : &Brick) -> u16{
-    if brick.orientation == Horizontal{
+    let width = if brick.orientation == Horizontal{
         brick.width_horizontal
     }
     else{
         brick.height_horizontal
+    };
+
+    return width * brick.scale;
+}
+
+//the field cells the brick covers at the given position, every vertex taking a block of scale × scale cells
+pub fn brick_cells(brick : &Brick, master_node_position : &Point) -> Vec<Point>{
+    let scale = brick.scale as i32;
+    let mut scaled : Vec<Point> = vec![];
+
+    for vertex in brick.vertices.iter(){
+        for dx in 0..scale {
+            for dy in 0..scale {
+                scaled.push(Point{x: vertex.x * scale + dx, y: vertex.y * scale + dy});
+            }
+        }
     }
+
+    return get_screen_translated_vertices(&scaled, master_node_position);
 }
 
-fn calculate_width(vertices : &Vec<Point>) -> u16{
+fn calculate_width(vertices : &[Point]) -> u16{
     let mut min_x = 100;
     let mut max_x = -100;
 
@@ -134,7 +85,7 @@ fn calculate_width(vertices : &Vec<Point>) -> u16{
     return (max_x - min_x + 1) as u16;
 }
 
-fn calculate_height(vertices : &Vec<Point>) -> u16{
+fn calculate_height(vertices : &[Point]) -> u16{
     let mut min_y = 100;
     let mut max_y = -100;
 
@@ -151,45 +102,52 @@ fn calculate_height(vertices : &Vec<Point>) -> u16{
     return (max_y - min_y + 1) as u16;
 }
 
-pub fn rotate(brick: &mut Brick){
-    let master_node = find_future_master_node(&brick.vertices);
-    for vertex in brick.vertices.iter_mut(){
-        let old_x = vertex.x;
+//the middle of the bounding box, doubled; when it falls between a cell and a corner it moves down or left to the cell,
+//so that any cell set turns onto the grid
+pub fn derive_centre(vertices : &[Point]) -> Point{
+    let mut centre = Point{x: calculate_width(vertices) as i32 - 1, y: calculate_height(vertices) as i32 - 1};
 
-        vertex.x = vertex.y;
-        vertex.y = master_node.x - (old_x + 1);
+    if (centre.x - centre.y) % 2 != 0 {
+        if centre.x % 2 != 0 {
+            centre.x -= 1;
+        }
+        else {
+            centre.y -= 1;
+        }
     }
 
-    if brick.orientation == Horizontal {
-        brick.orientation = Vertical;
-    }
-    else {
-        brick.orientation = Horizontal;
-    }
+    return centre;
 }
 
-fn find_future_master_node(vertices : &Vec<Point>) -> Point{
-    //max old x & min old y will become new master node coordinates after the bricks rotation
-    //master node is in the bottom left corner of a rectangle drawn over the brick
+//turns any polyomino a quarter clockwise around its centre; returns how far the master node has to move (in vertices)
+//for the brick to turn in place
+pub fn rotate(brick: &mut Brick) -> Point{
+    let centre = brick.centre;
 
-    let mut p: Point = vertices[0];
+    //(x, y) -> (cx + y - cy, cy - x + cx), in doubled coordinates
+    let turned : Vec<Point> = brick.vertices.iter()
+        .map(|vertex| Point{x: (centre.x + 2 * vertex.y - centre.y) / 2, y: (centre.y - 2 * vertex.x + centre.x) / 2})
+        .collect();
+    let min_x = turned.iter().map(|vertex| vertex.x).min().unwrap();
+    let min_y = turned.iter().map(|vertex| vertex.y).min().unwrap();
 
-    for vertex in vertices.iter() {
-        if vertex.x > p.x {
-            p.x = vertex.x;
-        }
-
-        if vertex.y < p.y {
-            p.y = vertex.y;
-        }
-    }
+    brick.vertices = turned.iter().map(|vertex| Point{x: vertex.x - min_x, y: vertex.y - min_y}).collect();
+    brick.centre = Point{x: centre.x - 2 * min_x, y: centre.y - 2 * min_y};
+    toggle_orientation(brick);
 
-    p.x += 1;
+    return Point{x: min_x, y: -min_y};
+}
 
-    return p;
+fn toggle_orientation(brick: &mut Brick){
+    if brick.orientation == Horizontal {
+        brick.orientation = Vertical;
+    }
+    else {
+        brick.orientation = Horizontal;
+    }
 }
 
-pub fn find_dead_master_node(vertices : &Vec<Point>) -> Point{
+pub fn find_dead_master_node(vertices : &[Point]) -> Point{
     //master node is in the bottom left corner of a rectangle drawn over the brick
     let mut p: Point = vertices[0];
 
@@ -206,22 +164,18 @@ pub fn find_dead_master_node(vertices : &Vec<Point>) -> Point{
     return p;
 }
 
-pub fn print_brick(brick : &Brick, master_node_position : &Point){
-    clear_console();
-
-    let vertices = get_screen_translated_vertices(&brick.vertices, master_node_position);
+pub fn print_brick(brick : &Brick, master_node_position : &Point, screen : &Screen){
+    let vertices = brick_cells(brick, master_node_position);
 
-    print_dead_brick(&DeadBrick{vertices, color: brick.color});
+    print_dead_brick(&DeadBrick{vertices, kind: brick.kind, locked_at: 0}, screen);
 }
 
-pub fn print_dead_brick(brick: &DeadBrick){
-    let mut console = io::stdout();
+pub fn print_ghost(brick : &Brick, master_node_position : &Point, screen : &Screen){
+    let vertices = brick_cells(brick, master_node_position);
 
-    console.execute(style::SetForegroundColor(brick.color)).unwrap();
-
-    for point in brick.vertices.iter(){
-        move_to_and_write(point.x, point.y, "*");
-    }
+    write_points(screen, &vertices, screen.glyphs.ghost, screen.theme.ghost);
+}
 
-    console.execute(style::SetForegroundColor(Color::White)).unwrap();
+pub fn print_dead_brick(brick: &DeadBrick, screen : &Screen){
+    write_points(screen, &brick.vertices, screen.glyphs.cell, piece_color(&screen.theme, brick.kind));
 }
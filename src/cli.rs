@@ -0,0 +1,226 @@
+use std::path::PathBuf;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde::{Deserialize, Serialize};
+
+use crate::config::Override;
+use crate::puzzle::{load_puzzle, Puzzle};
+
+#[derive(Parser)]
+#[command(name = "tetris", version, about = "Tetris in your terminal")]
+pub struct Cli{
+    #[command(subcommand)]
+    pub(crate) command : Option<Command>, //plays with the default options when missing
+    /// Configuration file to read instead of $XDG_CONFIG_HOME/tetris/config.toml
+    #[arg(long, global = true, value_name = "FILE")]
+    pub(crate) config : Option<PathBuf>,
+    /// Override a setting of the configuration file, e.g. --set handling.das=120
+    #[arg(long = "set", global = true, value_name = "KEY=VALUE")]
+    pub(crate) settings : Vec<String>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Play a game
+    Play(PlayArgs),
+    /// Play back a recorded game
+    Replay {
+        file : PathBuf,
+    },
+    /// Measure how fast the engine runs by letting the bot play without a screen
+    Bench {
+        /// Number of bricks to place
+        #[arg(long, default_value_t = 10000)]
+        pieces : u32,
+        /// Print the field at the end in the text board format
+        #[arg(long)]
+        print_board : bool,
+        #[command(flatten)]
+        game : GameArgs,
+    },
+    /// Watch the bot play
+    Bot {
+        /// Delay between the bot's key presses in ms
+        #[arg(long, default_value_t = 80)]
+        delay : u64,
+        #[command(flatten)]
+        mode : ModeArgs,
+        #[command(flatten)]
+        game : GameArgs,
+    },
+    /// Show the high score tables
+    Scores {
+        /// Only the tables of this mode
+        #[arg(long)]
+        mode : Option<ModeName>,
+        /// Rows shown per table
+        #[arg(long, default_value_t = 10)]
+        rows : usize,
+    },
+    /// Convert between fumen strings and text boards
+    Fumen {
+        #[command(subcommand)]
+        action : FumenCommand,
+    },
+    /// Show or create the configuration file
+    Config {
+        #[command(subcommand)]
+        action : Option<ConfigCommand>,
+    },
+}
+
+#[derive(Subcommand, Clone, Copy)]
+pub enum ConfigCommand {
+    /// Print the location of the configuration file
+    Path,
+    /// Print the configuration in effect
+    Show,
+    /// Write the default configuration to the configuration file
+    Init,
+}
+
+#[derive(Subcommand, Clone)]
+pub enum FumenCommand {
+    /// Print every page of a fumen (v115@...) as a text board
+    Decode {
+        fumen : String,
+    },
+    /// Print a fumen with a page for each text board file
+    Encode {
+        #[arg(required = true)]
+        files : Vec<PathBuf>,
+    },
+}
+
+#[derive(Copy, Clone, PartialEq, ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ModeName {
+    /// Endless game, gravity speeds up with every row the brick descends
+    Classic,
+    /// Clear 150 lines while the level goes up every 10
+    Marathon,
+    /// Clear 40 lines as fast as possible
+    Sprint,
+    /// Score as much as possible in 2 minutes
+    Ultra,
+    /// Slow and endless: topping out clears the bottom rows, quitting saves the board
+    Zen,
+    /// Dig through rows of garbage as fast as possible
+    Dig,
+    /// Play with the 18 one-sided pentominoes on a wider field
+    Pentomino,
+    /// Reach the goal of a set-piece drill with a fixed piece sequence
+    Puzzle,
+}
+
+#[derive(Args, Clone, Default)]
+pub struct ModeArgs{
+    /// Game mode, chosen from a menu when missing
+    #[arg(long, value_enum)]
+    pub(crate) mode : Option<ModeName>,
+    /// Play marathon without its line goal
+    #[arg(long)]
+    pub(crate) endless : bool,
+    /// Garbage rows to dig through in dig mode [default: 10, at most the field height minus 4]
+    #[arg(long, value_name = "ROWS")]
+    pub(crate) garbage : Option<u16>,
+    /// Play pentomino mode with the 12 free pentominoes, mirror images left out
+    #[arg(long)]
+    pub(crate) free : bool,
+    /// Puzzle to play: tetris, perfect-clear, t-spin-triple, survive or a puzzle file; implies --mode puzzle [default: tetris]
+    #[arg(long, value_parser = load_puzzle)]
+    pub(crate) puzzle : Option<Puzzle>,
+}
+
+#[derive(Args, Clone)]
+pub struct GameArgs{
+    /// Seed of the brick sequence, random when missing
+    #[arg(long)]
+    pub(crate) seed : Option<u64>,
+    /// Number of columns of the field [default: rules.width]
+    #[arg(long, value_parser = clap::value_parser!(u16).range(4..=40))]
+    pub(crate) width : Option<u16>,
+    /// Number of rows of the field [default: rules.height]
+    #[arg(long, value_parser = clap::value_parser!(u16).range(4..=40))]
+    pub(crate) height : Option<u16>,
+    /// Starting level, higher levels start with faster gravity [default: rules.level]
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..=20))]
+    pub(crate) level : Option<u32>,
+    /// Piece set: legacy, tetrominoes, trominoes, pentominoes or a piece set file [default: rules.pieces]
+    #[arg(long, value_name = "SET")]
+    pub(crate) piece_set : Option<String>,
+    /// Big mode: every brick cell takes 2 × 2 field cells and bricks move in 2-cell steps
+    #[arg(long)]
+    pub(crate) big : bool,
+    /// Landed bricks disappear as soon as they lock and show again at game over
+    #[arg(long, conflicts_with = "fade")]
+    pub(crate) invisible : bool,
+    /// Landed bricks disappear this many seconds after they lock and show again at game over
+    #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(0..=60))]
+    pub(crate) fade : Option<u64>,
+}
+
+#[derive(Args, Clone)]
+pub struct PlayArgs{
+    #[command(flatten)]
+    pub(crate) mode : ModeArgs,
+    #[command(flatten)]
+    pub(crate) game : GameArgs,
+    /// Print the game as a fumen at the end, a page for every placed brick
+    #[arg(long)]
+    pub(crate) fumen : bool,
+    /// Save a replay of the game for `tetris replay` [default: session.record_replays]
+    #[arg(long)]
+    pub(crate) record : bool,
+    /// Continue the game saved on quit, with the mode and rules it was played with
+    #[arg(long, conflicts_with_all = ["mode", "puzzle", "seed"])]
+    pub(crate) resume : bool,
+    /// Race a sprint against a recorded replay, or `best` for your fastest recorded sprint
+    #[arg(long, value_name = "REPLAY", conflicts_with_all = ["puzzle", "resume"])]
+    pub(crate) ghost : Option<String>,
+}
+
+impl Default for PlayArgs {
+    fn default() -> Self {
+        PlayArgs{mode: ModeArgs::default(), game: GameArgs{seed: None, width: None, height: None, level: None, piece_set: None, big: false, invisible: false, fade: None}, fumen: false, record: false, resume: false, ghost: None}
+    }
+}
+
+pub fn game_args(command : &Command) -> Option<&GameArgs>{
+    match command {
+        Command::Play(args) => Some(&args.game),
+        Command::Bench { game, .. } | Command::Bot { game, .. } => Some(game),
+        _ => None
+    }
+}
+
+//the game flags take precedence over the configuration
+pub fn game_overrides(args : &GameArgs) -> Vec<Override>{
+    let mut overrides : Vec<Override> = vec![];
+    let mut add = |flag : &str, key : &str, value : Option<i64>| {
+        if let Some(value) = value {
+            overrides.push(Override{source: String::from(flag), key: String::from(key), value: toml::Value::Integer(value)});
+        }
+    };
+
+    add("--width", "rules.width", args.width.map(i64::from));
+    add("--height", "rules.height", args.height.map(i64::from));
+    add("--level", "rules.level", args.level.map(i64::from));
+    add("--fade", "rules.fade_ms", args.fade.map(|seconds| seconds as i64 * 1000));
+
+    if let Some(pieces) = &args.piece_set {
+        overrides.push(Override{source: String::from("--piece-set"), key: String::from("rules.pieces"), value: toml::Value::String(pieces.clone())});
+    }
+
+    if args.big {
+        overrides.push(Override{source: String::from("--big"), key: String::from("rules.big"), value: toml::Value::Boolean(true)});
+    }
+
+    let board = if args.invisible { Some("invisible") } else { args.fade.map(|_| "fading") };
+
+    if let Some(board) = board {
+        let flag = if args.invisible { "--invisible" } else { "--fade" };
+        overrides.push(Override{source: String::from(flag), key: String::from("rules.board"), value: toml::Value::String(String::from(board))});
+    }
+
+    return overrides;
+}
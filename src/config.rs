@@ -0,0 +1,371 @@
+use std::collections::HashMap;
+use std::env;
+use std::fmt::Display;
+use std::fs;
+use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+
+use crate::animation::AnimationSettings;
+use crate::field::{BoardVisibility, ClearGravity};
+use crate::game::{GravityMode, Randomizer, RotationSystem};
+use crate::input::{build_bindings, KeySettings};
+use crate::pieces::{load_piece_set, PieceSet};
+use crate::screen::Style;
+use crate::theme::{apply_color_overrides, find_theme, Theme, THEME_NAMES};
+
+const ENV_PREFIX : &str = "TETRIS_"; //TETRIS_HANDLING__DAS=120 sets handling.das, "__" separates the keys
+
+#[derive(Deserialize, Serialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config{
+    pub(crate) handling : Handling,
+    pub(crate) visuals : Visuals,
+    pub(crate) rules : Rules,
+    pub(crate) keys : KeySettings,
+    pub(crate) session : SessionSettings,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Handling{
+    pub(crate) das : u64, //delayed auto shift: ms a move key is held before it repeats
+    pub(crate) arr : u64, //auto repeat rate: ms between repeated moves, 0 slides the brick to the wall
+    pub(crate) lock_delay : u64, //ms a brick may rest on the ground, 0 locks it at the next descent
+    pub(crate) mouse : bool, //pointer positions the brick, wheel rotates, left click hard drops, right click holds
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Visuals{
+    pub(crate) theme : String,
+    pub(crate) style : Style,
+    pub(crate) ghost : bool,
+    pub(crate) colors : HashMap<String, String>, //per-piece / ghost / border / hud overrides of the theme
+    pub(crate) animations : AnimationSettings,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Rules{
+    pub(crate) width : u16, //playable columns, without the borders
+    pub(crate) height : u16,
+    pub(crate) big : bool, //every brick cell takes 2 × 2 field cells, on a field twice as wide and high
+    pub(crate) level : u32, //starting level, sets the first interval
+    pub(crate) gravity : GravityMode,
+    pub(crate) decay : f64,
+    pub(crate) clear_gravity : ClearGravity,
+    pub(crate) board : BoardVisibility,
+    pub(crate) fade_ms : u64, //how long a landed brick shows on a fading board
+    pub(crate) garbage_messiness : f64, //0 keeps the holes of garbage rows in one column, 1 moves them every row
+    pub(crate) randomizer : Randomizer,
+    pub(crate) rotation : RotationSystem,
+    pub(crate) pieces : String, //a built-in piece set, a set in the pieces directory of the configuration or a path
+}
+
+#[derive(Deserialize, Serialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct SessionSettings{
+    pub(crate) save_on_quit : bool, //quitting saves the game for `tetris play --resume`
+    pub(crate) record_replays : bool, //every game is saved as a replay for `tetris replay`
+    pub(crate) player : String, //name in the high score table, the login name when empty
+}
+
+impl Default for Handling {
+    fn default() -> Self {
+        Handling{das: 170, arr: 50, lock_delay: 0, mouse: false}
+    }
+}
+
+impl Default for Visuals {
+    fn default() -> Self {
+        Visuals{theme: String::from("guideline"), style: Style::Ascii, ghost: true, colors: HashMap::new(), animations: AnimationSettings::default()}
+    }
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules{width: 5, height: 10, big: false, level: 1, gravity: GravityMode::Classic, decay: 0.999, clear_gravity: ClearGravity::Cascade, board: BoardVisibility::Visible, fade_ms: 5000, garbage_messiness: 0.3, randomizer: Randomizer::Random, rotation: RotationSystem::Classic, pieces: String::from("legacy")}
+    }
+}
+
+//a single setting from the environment or the command line, applied on top of the configuration file
+pub struct Override{
+    pub(crate) source : String, //named in error messages
+    pub(crate) key : String, //dotted path, e.g. "handling.das"
+    pub(crate) value : toml::Value,
+}
+
+pub fn config_dir() -> Option<PathBuf>{
+    //$XDG_CONFIG_HOME/tetris, falling back to ~/.config/tetris (or %APPDATA%\tetris on Windows)
+    let base = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => match env::var_os("HOME") {
+            Some(home) => PathBuf::from(home).join(".config"),
+            None => PathBuf::from(env::var_os("APPDATA")?)
+        }
+    };
+
+    return Some(base.join("tetris"));
+}
+
+//$XDG_DATA_HOME/tetris for what the game writes by itself, falling back to ~/.local/share/tetris (or %APPDATA%\tetris on Windows)
+pub fn data_dir() -> Option<PathBuf>{
+    let base = match env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => match env::var_os("HOME") {
+            Some(home) => PathBuf::from(home).join(".local").join("share"),
+            None => PathBuf::from(env::var_os("APPDATA")?)
+        }
+    };
+
+    return Some(base.join("tetris"));
+}
+
+pub fn config_path() -> Option<PathBuf>{
+    Some(config_dir()?.join("config.toml"))
+}
+
+//values are read as TOML ("120", "true", "[\"Space\", \"Enter\"]"), anything else is taken as a plain string
+fn parse_value(text : &str) -> toml::Value{
+    match format!("value = {}", text).parse::<toml::Table>() {
+        Ok(mut table) => table.remove("value").unwrap(),
+        Err(_) => toml::Value::String(String::from(text))
+    }
+}
+
+//parses "handling.das=120" as given to --set
+pub fn parse_override(text : &str) -> Result<Override, String>{
+    match text.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => Ok(Override{
+            source: format!("--set {}", key.trim()),
+            key: String::from(key.trim()),
+            value: parse_value(value.trim()),
+        }),
+        _ => Err(format!("--set {}: expected KEY=VALUE", text))
+    }
+}
+
+fn env_overrides() -> Vec<Override>{
+    let mut overrides : Vec<Override> = env::vars()
+        .filter_map(|(name, value)| {
+            let path = name.strip_prefix(ENV_PREFIX)?;
+
+            if !path.contains("__") {
+                return None;
+            }
+
+            Some(Override{key: path.to_lowercase().replace("__", "."), value: parse_value(&value), source: name})
+        })
+        .collect();
+
+    overrides.sort_by(|a, b| a.source.cmp(&b.source)); //the environment has no order of its own
+
+    return overrides;
+}
+
+fn set_value(table : &mut toml::Table, key : &str, value : toml::Value) -> Result<(), String>{
+    let mut parts : Vec<&str> = key.split('.').collect();
+    let last = parts.pop().unwrap();
+    let mut current = table;
+
+    for part in parts.iter() {
+        let entry = current.entry(String::from(*part)).or_insert_with(|| toml::Value::Table(toml::Table::new()));
+
+        current = match entry {
+            toml::Value::Table(inner) => inner,
+            _ => return Err(format!("{} is not a section", part))
+        };
+    }
+
+    current.insert(String::from(last), value);
+
+    return Ok(());
+}
+
+fn deserialize_config(table : &toml::Table) -> Result<Config, String>{
+    //the error names the key, e.g. "invalid type: string \"fast\", expected u64 in `handling.das`"
+    Config::deserialize(toml::Value::Table(table.clone())).map_err(|err| err.to_string().trim_end().replace('\n', " "))
+}
+
+fn read_config_file(path : &PathBuf) -> Result<toml::Table, String>{
+    let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+
+    //parsing into the configuration first reports the offending key together with its line
+    toml::from_str::<Config>(&text).map_err(|err| format!("{}: {}", path.display(), err))?;
+
+    return text.parse::<toml::Table>().map_err(|err| format!("{}: {}", path.display(), err));
+}
+
+//defaults, then the configuration file, then TETRIS_* environment variables, then the command line
+pub fn load_config(file : Option<&PathBuf>, overrides : &[Override]) -> Result<Config, String>{
+    let mut table = match file {
+        Some(path) => read_config_file(path)?,
+        None => match config_path() {
+            Some(path) if path.exists() => read_config_file(&path)?,
+            _ => toml::Table::new()
+        }
+    };
+
+    for setting in env_overrides().iter().chain(overrides.iter()) {
+        set_value(&mut table, &setting.key, setting.value.clone()).map_err(|err| format!("{}: {}", setting.source, err))?;
+        deserialize_config(&table).map_err(|err| format!("{}: {}", setting.source, err))?;
+    }
+
+    let config = deserialize_config(&table)?;
+    validate_config(&config)?;
+
+    return Ok(config);
+}
+
+fn check_range<T : PartialOrd + Display>(key : &str, value : T, min : T, max : T) -> Result<(), String>{
+    if value < min || value > max {
+        return Err(format!("{}: must be between {} and {}, got {}", key, min, max, value));
+    }
+
+    return Ok(());
+}
+
+pub fn validate_config(config : &Config) -> Result<(), String>{
+    check_range("handling.das", config.handling.das, 0, 2000)?;
+    check_range("handling.arr", config.handling.arr, 0, 1000)?;
+    check_range("handling.lock_delay", config.handling.lock_delay, 0, 5000)?;
+    check_range("visuals.animations.line_clear_ms", config.visuals.animations.line_clear_ms, 0, 5000)?;
+    check_range("visuals.animations.lock_flash_ms", config.visuals.animations.lock_flash_ms, 0, 5000)?;
+    check_range("visuals.animations.top_out_ms", config.visuals.animations.top_out_ms, 0, 5000)?;
+    check_range("rules.width", config.rules.width, 4, 40)?;
+    check_range("rules.height", config.rules.height, 4, 40)?;
+    check_range("rules.level", config.rules.level, 1, 20)?;
+    check_range("rules.decay", config.rules.decay, 0.5, 1.0)?;
+    check_range("rules.fade_ms", config.rules.fade_ms, 0, 60000)?;
+    check_range("rules.garbage_messiness", config.rules.garbage_messiness, 0.0, 1.0)?;
+
+    build_theme(config, &load_piece_set(&config.rules.pieces)?)?;
+    build_bindings(&config.keys)?;
+
+    return Ok(());
+}
+
+pub fn show_config(config : &Config) -> String{
+    toml::to_string(config).expect("the configuration is serializable")
+}
+
+pub fn write_default_config(file : Option<&PathBuf>) -> Result<PathBuf, String>{
+    let path = match file {
+        Some(path) => path.clone(),
+        None => config_path().ok_or("Cannot determine the configuration directory")?
+    };
+
+    if path.exists() {
+        return Err(format!("{} already exists", path.display()));
+    }
+
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).map_err(|err| format!("{}: {}", path.display(), err))?;
+    }
+
+    fs::write(&path, show_config(&Config::default())).map_err(|err| format!("{}: {}", path.display(), err))?;
+
+    return Ok(path);
+}
+
+//the theme of the configuration with the colors of the piece set the game is played with
+pub fn build_theme(config : &Config, pieces : &PieceSet) -> Result<Theme, String>{
+    let mut theme = match find_theme(&config.visuals.theme) {
+        Some(theme) => theme,
+        None => return Err(format!("visuals.theme: unknown theme '{}', expected one of: {}", config.visuals.theme, THEME_NAMES.join(", ")))
+    };
+
+    theme.pieces.extend(pieces.colors.clone()); //the player's colors still win
+    apply_color_overrides(&mut theme, &config.visuals.colors)?;
+
+    return Ok(theme);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::style::Color;
+    use crate::pieces::parse_piece_set;
+
+    fn colored_set() -> PieceSet {
+        parse_piece_set("name = \"colored\"\n[[pieces]]\nname = \"T\"\ncells = [[0, 0], [1, 0], [2, 0], [1, 1]]\ncolor = \"#010203\"\n[[pieces]]\nname = \"P\"\ncells = [[0, 0], [1, 0], [0, 1], [1, 1], [0, 2]]\ncolor = \"#040506\"\n").unwrap()
+    }
+
+    #[test]
+    fn piece_set_colors_and_overrides_go_on_top_of_the_theme() {
+        let mut config = Config::default();
+        config.visuals.theme = String::from("guideline");
+        config.visuals.colors = HashMap::from([(String::from("t"), String::from("#0a0b0c")), (String::from("ghost"), String::from("240"))]);
+
+        let theme = build_theme(&config, &colored_set()).unwrap();
+
+        assert_eq!(theme.pieces[&'T'], Color::Rgb { r: 10, g: 11, b: 12 }); //the player's color beats the one of the set
+        assert_eq!(theme.pieces[&'P'], Color::Rgb { r: 4, g: 5, b: 6 });
+        assert_eq!(theme.pieces[&'I'], Color::Rgb { r: 0, g: 240, b: 240 }); //untouched colors stay those of the theme
+        assert_eq!(theme.ghost, Color::AnsiValue(240));
+    }
+
+    #[test]
+    fn unknown_themes_colors_and_targets_are_rejected() {
+        let mut config = Config::default();
+        config.visuals.theme = String::from("neon");
+        assert!(build_theme(&config, &colored_set()).is_err_and(|err| err.starts_with("visuals.theme: unknown theme 'neon'")));
+
+        config.visuals.theme = String::from("monochrome");
+        config.visuals.colors = HashMap::from([(String::from("ghost"), String::from("ultraviolet"))]);
+        assert_eq!(build_theme(&config, &colored_set()).err(), Some(String::from("visuals.colors.ghost: unknown color 'ultraviolet'")));
+
+        config.visuals.colors = HashMap::from([(String::from("shadow"), String::from("red"))]);
+        assert!(build_theme(&config, &colored_set()).is_err_and(|err| err.starts_with("visuals.colors.shadow: expected a piece letter")));
+    }
+
+    #[test]
+    fn overrides_parse_as_toml_values_or_else_as_text() {
+        let setting = parse_override(" handling.das = 120").unwrap();
+        assert_eq!((setting.source.as_str(), setting.key.as_str(), setting.value), ("--set handling.das", "handling.das", toml::Value::Integer(120)));
+
+        assert_eq!(parse_override("visuals.theme=neon").unwrap().value, toml::Value::String(String::from("neon")));
+        assert_eq!(parse_override("visuals.ghost=false").unwrap().value, toml::Value::Boolean(false));
+        assert_eq!(parse_override("rules.decay=0.75").unwrap().value, toml::Value::Float(0.75));
+        assert_eq!(parse_override("handling.das").err(), Some(String::from("--set handling.das: expected KEY=VALUE")));
+        assert!(parse_override(" =5").is_err());
+    }
+
+    #[test]
+    fn a_value_cannot_be_set_below_a_key_which_is_not_a_section() {
+        let mut table : toml::Table = "[handling]\ndas = 120\n".parse().unwrap();
+
+        assert_eq!(set_value(&mut table, "handling.das.fast", toml::Value::Integer(1)), Err(String::from("das is not a section")));
+        assert!(set_value(&mut table, "visuals.animations.line_clear_ms", toml::Value::Integer(0)).is_ok()); //missing sections are created
+        assert_eq!(table["visuals"]["animations"]["line_clear_ms"], toml::Value::Integer(0));
+    }
+
+    #[test]
+    fn values_out_of_their_range_are_rejected() {
+        let mut config = Config::default();
+        assert!(validate_config(&config).is_ok());
+
+        config.handling.das = 2001;
+        assert_eq!(validate_config(&config).err(), Some(String::from("handling.das: must be between 0 and 2000, got 2001")));
+
+        config.handling.das = 2000;
+        config.rules.decay = 0.25;
+        assert_eq!(validate_config(&config).err(), Some(String::from("rules.decay: must be between 0.5 and 1, got 0.25")));
+    }
+
+    #[test]
+    fn the_command_line_replaces_the_environment_which_replaces_the_file() {
+        let path = env::temp_dir().join(format!("tetris-config-test-{}.toml", std::process::id()));
+        fs::write(&path, "[handling]\ndas = 200\narr = 40\nlock_delay = 400\n").unwrap();
+        env::set_var("TETRIS_HANDLING__DAS", "150");
+        env::set_var("TETRIS_HANDLING__ARR", "5");
+
+        let config = load_config(Some(&path), &[parse_override("handling.das=90").unwrap()]);
+        env::remove_var("TETRIS_HANDLING__DAS");
+        env::remove_var("TETRIS_HANDLING__ARR");
+        fs::remove_file(&path).unwrap();
+
+        let handling = config.unwrap().handling;
+        assert_eq!((handling.das, handling.arr, handling.lock_delay), (90, 5, 400));
+    }
+}
@@ -1,8 +1,29 @@
-use crate::brick::{Brick, DeadBrick, find_dead_master_node, print_dead_brick};
+use serde::{Deserialize, Serialize};
+
+use crate::brick::{brick_cells, Brick, DeadBrick, find_dead_master_node, print_dead_brick};
 use crate::point::{are_touching, Point, translate_by};
-use crate::utility::{clear_console, move_to_and_write, get_screen_translated_vertices, get_translated_vertices};
+use crate::screen::{Screen, write_points};
+use crate::utility::get_translated_vertices;
+
+pub const GARBAGE_KIND : char = 'G'; //no theme has a color for it, so garbage shows in the fallback color
 
-#[derive(Clone)]
+#[derive(Copy, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ClearGravity { //what happens above cleared rows
+    Naive, //everything moves down by the number of cleared rows below it, floating cells stay afloat
+    Sticky, //connected cells of the same color stick together and fall as one piece until they land
+    Cascade, //every connected part of a brick falls on its own until it lands
+}
+
+#[derive(Copy, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BoardVisibility { //how landed bricks show, every one shows again once the game is over
+    Visible,
+    Fading, //landed bricks disappear a while after they lock
+    Invisible, //landed bricks disappear as soon as they lock
+}
+
+#[derive(Clone, Deserialize, Serialize)]
 pub struct Field{
     pub(crate) bricks : Vec<DeadBrick>,
     pub(crate) height : u16,
@@ -13,7 +34,7 @@ pub fn create_field(width : u16, height: u16) -> Field{
     Field{bricks: vec![], height, width}
 }
 
-pub fn will_have_collision(vertices : &Vec<Point>, field: &Field) -> bool {
+pub fn will_have_collision(vertices : &[Point], field: &Field) -> bool {
     for dead_brick in field.bricks.iter(){
         for dead_ver in dead_brick.vertices.iter() {
             for new_ver in vertices.iter() {
@@ -27,12 +48,10 @@ pub fn will_have_collision(vertices : &Vec<Point>, field: &Field) -> bool {
     return false;
 }
 
-pub fn land_brick(brick: &Brick, master_node_position : &Point, field: &mut Field){
-    let vertices = get_screen_translated_vertices(&brick.vertices, master_node_position);
-
-    field.bricks.push(DeadBrick { vertices, color: brick.color });
+pub fn land_brick(brick: &Brick, master_node_position : &Point, field: &mut Field, time : u64){
+    let vertices = brick_cells(brick, master_node_position);
 
-    remove_full_rows(field);
+    field.bricks.push(DeadBrick { vertices, kind: brick.kind, locked_at: time });
 }
 
 pub fn find_full_rows(field : &Field) -> Vec<i32>{
@@ -70,12 +89,22 @@ fn does_vertex_exist(field: &Field, row: i32, col: i32) -> bool {
 }
 
 pub fn can_descend_brick(brick : &Brick, master_node_pos : &Point, field: &Field) -> bool{
-    let translated_vertices = get_screen_translated_vertices(&brick.vertices, &master_node_pos);
+    let translated_vertices = brick_cells(brick, master_node_pos);
     let lowered_translated_vertices = get_translated_vertices(&translated_vertices, &Point{x: 0, y: 1});
 
     return ((master_node_pos.y + 1) < field.height as i32) && (!will_have_collision(&lowered_translated_vertices, field));
 }
 
+pub fn find_drop_position(brick : &Brick, master_node_pos : &Point, field: &Field) -> Point{
+    let mut drop_pos = *master_node_pos;
+
+    while can_descend_brick(brick, &drop_pos, field) {
+        translate_by(&mut drop_pos, &Point{x: 0, y: 1});
+    }
+
+    return drop_pos;
+}
+
 pub fn can_descend_dead_brick(field: &Field, idx : usize) -> bool{
     let brick_to_be_lowered = field.bricks.get(idx).unwrap();
     let master_node_pos = find_dead_master_node(&brick_to_be_lowered.vertices);
@@ -118,10 +147,10 @@ fn did_lower_dead_brick(field: &mut Field, idx : usize) -> bool{
 
 fn remove_empty_bricks(field: &mut Field){
     loop {
-        let pos = field.bricks.iter().position(|dead| dead.vertices.len() == 0);
+        let pos = field.bricks.iter().position(|dead| dead.vertices.is_empty());
 
-        if pos != None {
-            field.bricks.remove(pos.unwrap());
+        if let Some(pos) = pos {
+            field.bricks.remove(pos);
         }
         else {
             break;
@@ -129,50 +158,91 @@ fn remove_empty_bricks(field: &mut Field){
     }
 }
 
-fn split_disconnected_subbricks(field: &mut Field){
-    let bricks_copy = field.bricks.clone();
+//groups the cells into parts which are connected through their edges (4-neighbour flood fill)
+fn connected_components(cells : &[Point]) -> Vec<Vec<Point>>{
+    let mut remaining = cells.to_vec();
+    let mut components : Vec<Vec<Point>> = vec![];
 
-    for brick_idx in 0..bricks_copy.len(){
-        let brick = bricks_copy.get(brick_idx).unwrap();
-        let mut removed_cnt = 0;
+    while !remaining.is_empty() {
+        let mut component = vec![remaining.remove(0)];
+        let mut idx = 0;
 
-        for vertex_idx in 0..brick.vertices.len() {
-            let my_vertex= brick.vertices.get(vertex_idx).unwrap();
-            let mut touches_any_neighbour= false;
+        while idx < component.len() {
+            let cell = component[idx];
+            let (touching, rest) : (Vec<Point>, Vec<Point>) = remaining.into_iter().partition(|other| are_touching(&cell, other));
 
-            for other_vertex in brick.vertices.iter() {
-                if my_vertex == other_vertex {
-                    continue;
-                }
+            component.extend(touching);
+            remaining = rest;
+            idx += 1;
+        }
 
-                touches_any_neighbour |= are_touching(my_vertex, other_vertex);
-            }
+        components.push(component);
+    }
 
-            if touches_any_neighbour == false && brick.vertices.len() > 1 {
-                remove_vertex(field, brick_idx, vertex_idx - removed_cnt);
-                removed_cnt += 1;
-                field.bricks.push(DeadBrick{vertices: vec![*my_vertex], color: brick.color });
-            }
+    return components;
+}
+
+//every brick which a cleared row cut in pieces becomes one brick per piece
+fn split_disconnected_subbricks(field: &mut Field){
+    let mut split_bricks : Vec<DeadBrick> = vec![];
+
+    for brick in field.bricks.iter() {
+        for component in connected_components(&brick.vertices) {
+            split_bricks.push(DeadBrick{vertices: component, kind: brick.kind, locked_at: brick.locked_at});
         }
     }
+
+    field.bricks = split_bricks;
 }
 
-fn remove_vertex(field: &mut Field, brick_idx : usize, vertex_idx : usize) {
-    let brick = field.bricks.get_mut(brick_idx).unwrap();
-    brick.vertices.remove(vertex_idx);
+//merges touching cells of the same kind into one brick, whichever brick they came from
+fn stick_same_kinds(field: &mut Field){
+    let mut kinds : Vec<char> = vec![];
 
-    if brick.vertices.len() == 0 {
-        remove_empty_bricks(field);
+    for brick in field.bricks.iter() {
+        if !kinds.contains(&brick.kind) {
+            kinds.push(brick.kind);
+        }
     }
+
+    let mut stuck_bricks : Vec<DeadBrick> = vec![];
+
+    for kind in kinds {
+        let cells : Vec<Point> = field.bricks.iter()
+            .filter(|brick| brick.kind == kind)
+            .flat_map(|brick| brick.vertices.iter().copied())
+            .collect();
+
+        for component in connected_components(&cells) {
+            let locked_at = field.bricks.iter()
+                .filter(|brick| brick.kind == kind && brick.vertices.iter().any(|vertex| component.contains(vertex)))
+                .map(|brick| brick.locked_at)
+                .max()
+                .unwrap_or(0);
+
+            stuck_bricks.push(DeadBrick{vertices: component, kind, locked_at});
+        }
+    }
+
+    field.bricks = stuck_bricks;
 }
 
-pub fn remove_full_rows(field: &mut Field){
-    let full_rows = find_full_rows(field);
+fn lower_dead_bricks(field: &mut Field){
+    loop { //to make sure no bricks are blocking virtually
+        let mut any_brick_lowered = false;
 
-    if full_rows.len() == 0 {
-        return;
+        for i in 0..field.bricks.len() {
+            any_brick_lowered |= did_lower_dead_brick(field, i);
+        }
+
+        if !any_brick_lowered {
+            break;
+        }
     }
+}
 
+//removes the given rows and lets whatever was above them fall by the given rule; falling may complete further rows
+pub fn clear_rows(field: &mut Field, full_rows : &[i32], gravity : ClearGravity){
     for row in full_rows.iter(){
         for brick in field.bricks.iter_mut(){
             let mut new_vertices : Vec<Point> = vec![];
@@ -187,46 +257,309 @@ pub fn remove_full_rows(field: &mut Field){
     }
 
     remove_empty_bricks(field);
-    split_disconnected_subbricks(field);
 
-    loop { //to make sure no bricks are blocking virtually
-        let mut any_brick_lowered = false;
+    match gravity {
+        ClearGravity::Naive => {
+            for vertex in field.bricks.iter_mut().flat_map(|brick| brick.vertices.iter_mut()) {
+                let cleared_below = full_rows.iter().filter(|row| **row > vertex.y).count() as i32;
+                translate_by(vertex, &Point{x: 0, y: cleared_below});
+            }
+        },
 
-        for i in 0..field.bricks.len() {
-            any_brick_lowered |= did_lower_dead_brick(field, i);
+        ClearGravity::Sticky => {
+            stick_same_kinds(field);
+            lower_dead_bricks(field);
+        },
+
+        ClearGravity::Cascade => {
+            split_disconnected_subbricks(field);
+            lower_dead_bricks(field);
+        },
+    }
+}
+
+//raises the stack by one row per hole and fills the new bottom rows except for the hole columns (top row first);
+//a hole spans hole_width columns from its own
+pub fn insert_garbage_rows(field: &mut Field, holes : &[i32], hole_width : i32, time : u64){
+    let count = holes.len() as i32;
+
+    for vertex in field.bricks.iter_mut().flat_map(|brick| brick.vertices.iter_mut()) {
+        translate_by(vertex, &Point{x: 0, y: -count});
+    }
+
+    for (idx, hole) in holes.iter().enumerate() {
+        let y = field.height as i32 - count + idx as i32;
+        let left : Vec<Point> = (1..*hole).map(|x| Point{x, y}).collect();
+        let right : Vec<Point> = ((*hole + hole_width)..(field.width as i32 - 1)).map(|x| Point{x, y}).collect();
+
+        for part in [left, right] {
+            if !part.is_empty() {
+                field.bricks.push(DeadBrick{vertices: part, kind: GARBAGE_KIND, locked_at: time});
+            }
         }
+    }
+}
 
-        if !any_brick_lowered {
-            break;
+//puts landed cells of the given kinds on the field, touching cells of the same kind making one brick
+pub fn place_cells(field: &mut Field, cells : &[(Point, char)], time : u64){
+    let mut kinds : Vec<char> = cells.iter().map(|(_, kind)| *kind).collect();
+    kinds.sort();
+    kinds.dedup();
+
+    for kind in kinds {
+        let same_kind : Vec<Point> = cells.iter().filter(|(_, other)| *other == kind).map(|(cell, _)| *cell).collect();
+
+        for component in connected_components(&same_kind) {
+            field.bricks.push(DeadBrick{vertices: component, kind, locked_at: time});
         }
     }
+}
+
+//whether clearing the full rows leaves nothing on the field
+pub fn is_perfect_clear(field: &Field) -> bool{
+    let full_rows = find_full_rows(field);
 
-    remove_full_rows(field);
+    !full_rows.is_empty() && field.bricks.iter().flat_map(|brick| brick.vertices.iter()).all(|vertex| full_rows.contains(&vertex.y))
+}
 
-    clear_console();
+//whether the stack reaches above the top row
+pub fn is_overflowing(field: &Field) -> bool{
+    field.bricks.iter().flat_map(|brick| brick.vertices.iter()).any(|vertex| vertex.y < 0)
 }
 
-fn print_frame(field: &Field) {
-    //left border
+//how many rows still hold garbage
+pub fn count_garbage_rows(field: &Field) -> usize{
+    let mut rows : Vec<i32> = field.bricks.iter()
+        .filter(|brick| brick.kind == GARBAGE_KIND)
+        .flat_map(|brick| brick.vertices.iter().map(|vertex| vertex.y))
+        .collect();
+    rows.sort();
+    rows.dedup();
+
+    return rows.len();
+}
+
+//removes the given number of rows at the bottom and lowers everything above by as many rows
+pub fn drop_bottom_rows(field: &mut Field, count : u16){
+    let count = count.min(field.height) as i32;
+    let first_dropped = field.height as i32 - count;
+
+    for brick in field.bricks.iter_mut(){
+        brick.vertices.retain(|vertex| vertex.y < first_dropped);
+
+        for vertex in brick.vertices.iter_mut(){
+            translate_by(vertex, &Point{x: 0, y: count});
+        }
+    }
+
+    remove_empty_bricks(field);
+}
+
+//the border: the first and the last column of the field, which bricks never reach, and the row below it
+fn frame_cells(field: &Field) -> Vec<Point> {
+    let mut cells : Vec<Point> = vec![];
+
     for row in 0..field.height{
-        move_to_and_write(0, row as i32, "+");
+        cells.push(Point{x: 0, y: row as i32});
+        cells.push(Point{x: field.width as i32 - 1, y: row as i32});
     }
 
-    //bottom
-    for col in 0..(field.width + 1){
-        move_to_and_write(col as i32, field.height as i32, "+");
+    for col in 0..field.width{
+        cells.push(Point{x: col as i32, y: field.height as i32});
     }
 
-    //right border
-    for row in 0..field.height{
-        move_to_and_write(field.width as i32, row as i32, "+");
+    return cells;
+}
+
+fn print_frame(field: &Field, screen : &Screen) {
+    write_points(screen, &frame_cells(field), screen.glyphs.border, screen.theme.border);
+}
+
+//visible tells for every landed brick whether its cells are shown
+pub fn print_field(field: &Field, screen : &Screen, visible : &dyn Fn(&DeadBrick) -> bool){
+    print_frame(field, screen);
+
+    for brick in field.bricks.iter().filter(|brick| visible(brick)) {
+        print_dead_brick(brick, screen);
     }
 }
 
-pub fn print_field(field: &Field){
-    print_frame(field);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::brick::rotate;
+    use crate::cli::{ModeArgs, ModeName};
+    use crate::config::Config;
+    use crate::game::{apply_action, hard_drop, update_game};
+    use crate::input::Action;
+    use crate::mode::create_mode;
+    use crate::pieces::{load_piece_set, PIECE_SET_NAMES};
+    use crate::play::new_game;
+
+    const NEIGHBOURS : [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+
+    fn has_cell(cells : &[Point], x : i32, y : i32) -> bool {
+        cells.iter().any(|cell| cell.x == x && cell.y == y)
+    }
 
-    for brick in field.bricks.iter() {
-        print_dead_brick(&brick);
+    //walks the cells from the first one, independently of connected_components
+    fn is_connected(cells : &[Point]) -> bool {
+        let mut reached = vec![cells[0]];
+        let mut idx = 0;
+
+        while idx < reached.len() {
+            let cell = reached[idx];
+
+            for (dx, dy) in NEIGHBOURS {
+                if has_cell(cells, cell.x + dx, cell.y + dy) && !has_cell(&reached, cell.x + dx, cell.y + dy) {
+                    reached.push(Point{x: cell.x + dx, y: cell.y + dy});
+                }
+            }
+
+            idx += 1;
+        }
+
+        return reached.len() == cells.len();
+    }
+
+    fn pieces_touch(a : &DeadBrick, b : &DeadBrick) -> bool {
+        a.vertices.iter().any(|cell| NEIGHBOURS.iter().any(|(dx, dy)| has_cell(&b.vertices, cell.x + dx, cell.y + dy)))
+    }
+
+    fn cut(cells : &[Point], row : i32) -> Field {
+        let mut field = create_field(10, 10);
+        field.bricks.push(DeadBrick{vertices: cells.iter().filter(|cell| cell.y != row).copied().collect(), kind: 'T', locked_at: 0});
+        remove_empty_bricks(&mut field);
+        split_disconnected_subbricks(&mut field);
+
+        return field;
+    }
+
+    #[test]
+    fn a_cell_does_not_touch_itself() {
+        let cell = Point{x: 2, y: 3};
+
+        assert!(!are_touching(&cell, &cell));
+        assert!(are_touching(&cell, &Point{x: 2, y: 4}));
+        assert!(!are_touching(&cell, &Point{x: 3, y: 4}));
+    }
+
+    #[test]
+    fn every_shape_cut_at_every_row_splits_into_separate_connected_pieces() {
+        for brick in PIECE_SET_NAMES.iter().flat_map(|name| load_piece_set(name).unwrap().bricks) {
+            let mut brick = brick.clone();
+
+            for _ in 0..4 {
+                let cells = brick_cells(&brick, &Point{x: 4, y: 6});
+                let mut rows : Vec<i32> = cells.iter().map(|cell| cell.y).collect();
+                rows.sort();
+                rows.dedup();
+
+                for row in rows {
+                    let field = cut(&cells, row);
+                    let left_over = cells.iter().filter(|cell| cell.y != row).count();
+
+                    assert_eq!(field.bricks.iter().map(|piece| piece.vertices.len()).sum::<usize>(), left_over);
+
+                    for (idx, piece) in field.bricks.iter().enumerate() {
+                        assert!(is_connected(&piece.vertices), "shape {} cut at row {} left a disconnected piece", brick.kind, row);
+
+                        for other in field.bricks.iter().skip(idx + 1) {
+                            assert!(!pieces_touch(piece, other), "shape {} cut at row {} left touching pieces apart", brick.kind, row);
+                        }
+                    }
+                }
+
+                rotate(&mut brick);
+            }
+        }
+    }
+
+    #[test]
+    fn a_vertical_line_cut_in_the_middle_falls_as_two_pieces() {
+        let cells = vec![Point{x: 1, y: 3}, Point{x: 1, y: 4}, Point{x: 1, y: 5}];
+
+        assert_eq!(cut(&cells, 4).bricks.len(), 2);
+        assert_eq!(cut(&cells, 3).bricks.len(), 1);
+        assert_eq!(cut(&cells, 5).bricks.len(), 1);
+    }
+
+    #[test]
+    fn cascade_lets_an_unsupported_group_fall_on_its_own() {
+        //two bars of two cells joined by a full row: the left bar hangs in the air, the right one stands on the floor
+        let mut field = create_field(8, 6);
+        field.bricks.push(DeadBrick{vertices: vec![
+            Point{x: 1, y: 1}, Point{x: 1, y: 2},
+            Point{x: 1, y: 3}, Point{x: 2, y: 3}, Point{x: 3, y: 3},
+            Point{x: 3, y: 4}, Point{x: 3, y: 5},
+        ], kind: 'S', locked_at: 0});
+        field.bricks.push(DeadBrick{vertices: vec![Point{x: 4, y: 3}, Point{x: 5, y: 3}, Point{x: 6, y: 3}], kind: 'I', locked_at: 0});
+
+        let full_rows = find_full_rows(&field);
+        clear_rows(&mut field, &full_rows, ClearGravity::Cascade);
+
+        let cells : Vec<Point> = field.bricks.iter().flat_map(|brick| brick.vertices.iter().copied()).collect();
+        assert_eq!(cells.len(), 4);
+
+        for (x, y) in [(1, 4), (1, 5), (3, 4), (3, 5)] {
+            assert!(has_cell(&cells, x, y), "expected a cell at ({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn naive_gravity_moves_rows_down_by_the_cleared_rows_below() {
+        let mut field = create_field(5, 6);
+        field.bricks.push(DeadBrick{vertices: vec![Point{x: 1, y: 1}], kind: 'O', locked_at: 0}); //floating, stays afloat
+        field.bricks.push(DeadBrick{vertices: vec![Point{x: 1, y: 5}, Point{x: 2, y: 5}, Point{x: 3, y: 5}], kind: 'I', locked_at: 0});
+
+        let full_rows = find_full_rows(&field);
+        clear_rows(&mut field, &full_rows, ClearGravity::Naive);
+
+        assert_eq!(field.bricks.len(), 1);
+        assert!(has_cell(&field.bricks[0].vertices, 1, 2));
+    }
+
+    #[test]
+    fn garbage_rows_raise_the_stack_and_leave_their_hole_open() {
+        let mut field = create_field(6, 6);
+        field.bricks.push(DeadBrick{vertices: vec![Point{x: 2, y: 5}], kind: 'O', locked_at: 0});
+
+        insert_garbage_rows(&mut field, &[1, 3], 1, 0);
+
+        let cells : Vec<Point> = field.bricks.iter().flat_map(|brick| brick.vertices.iter().copied()).collect();
+        assert!(has_cell(&cells, 2, 3));
+        assert!(!has_cell(&cells, 1, 4) && !has_cell(&cells, 3, 5));
+        assert_eq!(cells.len(), 1 + 2 * 3);
+        assert_eq!(count_garbage_rows(&field), 2);
+        assert!(find_full_rows(&field).is_empty());
+        assert!(!is_overflowing(&field));
+    }
+
+    #[test]
+    fn the_border_sits_right_next_to_the_bricks_at_the_walls() {
+        let mut config = Config::default();
+        config.rules.width = 10;
+        config.rules.height = 20;
+        config.rules.pieces = String::from("tetrominoes");
+        let mut game = new_game(&config, Some(3), create_mode(ModeName::Classic, &ModeArgs::default()));
+        update_game(&mut game, 0);
+
+        for (step, action) in [Action::MoveLeft, Action::MoveRight].into_iter().enumerate() {
+            for _ in 0..game.field.width {
+                apply_action(&mut game, action);
+            }
+
+            hard_drop(&mut game);
+            update_game(&mut game, 1000 * (step as u64 + 1));
+        }
+
+        let frame = frame_cells(&game.field);
+        let cells : Vec<Point> = game.field.bricks.iter().flat_map(|brick| brick.vertices.clone()).collect();
+
+        assert_eq!(cells.iter().map(|cell| cell.x).min(), Some(1));
+        assert_eq!(cells.iter().map(|cell| cell.x).max(), Some(game.field.width as i32 - 2));
+        assert!(cells.iter().all(|cell| !has_cell(&frame, cell.x, cell.y)));
+        assert!(cells.iter().all(|cell| has_cell(&frame, cell.x - 1, cell.y) == (cell.x == 1) && has_cell(&frame, cell.x + 1, cell.y) == (cell.x == game.field.width as i32 - 2)));
+        assert!(has_cell(&frame, game.field.width as i32 - 1, game.field.height as i32)); //the corner closes the frame
     }
 }
@@ -0,0 +1,616 @@
+use crate::board::{field_grid, parse_board, render_board, Board, EMPTY_CELL};
+use crate::brick::DeadBrick;
+use crate::field::{create_field, place_cells, Field, GARBAGE_KIND};
+use crate::point::Point;
+
+//fumen v115: a 10 × 23 field with a garbage row below it, written as base64 digits with the least significant one first;
+//every page holds the changes to the field, the piece placed on it, flags and, when it changes, a comment
+const WIDTH : usize = 10;
+const TOP : usize = 23; //rows above the garbage row
+const BLOCKS : usize = WIDTH * (TOP + 1);
+const DIGITS : &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const COMMENT_CHARS : &str = " !\"#$%&'()*+,-./0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_`abcdefghijklmnopqrstuvwxyz{|}~";
+const COMMENT_BASE : u32 = 96;
+const MAX_COMMENT : usize = 4095;
+const PIECES : [char; 9] = ['.', 'I', 'L', 'O', 'Z', 'T', 'J', 'S', GARBAGE_KIND]; //by fumen code
+const UNCHANGED : u32 = 8 * BLOCKS as u32 + BLOCKS as u32 - 1; //one run of unchanged cells over the whole field
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Rotation { //in the order of their fumen codes
+    Reverse,
+    Right,
+    Spawn,
+    Left,
+}
+
+const ROTATIONS : [Rotation; 4] = [Rotation::Reverse, Rotation::Right, Rotation::Spawn, Rotation::Left];
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct FumenPiece{
+    pub(crate) kind : char,
+    pub(crate) rotation : Rotation,
+    pub(crate) x : i32, //of the rotation centre, from the left column
+    pub(crate) y : i32, //of the rotation centre, from the bottom row
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct FumenPage{
+    pub(crate) cells : Vec<u8>, //fumen codes from the top left, the garbage row last
+    pub(crate) piece : Option<FumenPiece>,
+    pub(crate) comment : String, //the last comment carries over to the following pages
+    pub(crate) lock : bool, //the piece is placed and full rows are cleared for the next page
+    pub(crate) rise : bool, //the garbage row rises into the field for the next page
+    pub(crate) mirror : bool, //the field is mirrored for the next page
+    pub(crate) colorize : bool,
+}
+
+pub fn create_page(cells : Vec<u8>) -> FumenPage{
+    FumenPage{cells, piece: None, comment: String::new(), lock: true, rise: false, mirror: false, colorize: false}
+}
+
+//the cells of the piece around its rotation centre, y up, as the fumen tools place them
+fn piece_blocks(kind : char, rotation : Rotation) -> Vec<(i32, i32)>{
+    let blocks : [(i32, i32); 4] = match kind {
+        'I' => [(0, 0), (-1, 0), (1, 0), (2, 0)],
+        'T' => [(0, 0), (-1, 0), (1, 0), (0, 1)],
+        'O' => [(0, 0), (1, 0), (0, 1), (1, 1)],
+        'L' => [(0, 0), (-1, 0), (1, 0), (1, 1)],
+        'J' => [(0, 0), (-1, 0), (1, 0), (-1, 1)],
+        'S' => [(0, 0), (-1, 0), (0, 1), (1, 1)],
+        'Z' => [(0, 0), (1, 0), (0, 1), (-1, 1)],
+        _ => return vec![]
+    };
+
+    blocks.iter().map(|(x, y)| match rotation {
+        Rotation::Spawn => (*x, *y),
+        Rotation::Right => (*y, -*x),
+        Rotation::Reverse => (-*x, -*y),
+        Rotation::Left => (-*y, *x),
+    }).collect()
+}
+
+fn piece_code(kind : char) -> u8{
+    PIECES[1..8].iter().position(|piece| *piece == kind).map_or(8, |idx| idx as u8 + 1) //anything else shows gray
+}
+
+//index of a cell into the page cells; y counts from the bottom row, -1 is the garbage row
+fn cell_index(x : i32, y : i32) -> Option<usize>{
+    if x < 0 || x >= WIDTH as i32 || y < -1 || y >= TOP as i32 {
+        return None;
+    }
+
+    Some((TOP as i32 - 1 - y) as usize * WIDTH + x as usize)
+}
+
+//the centre position as stored in the data, which differs for the pieces whose centre the old fumen placed elsewhere
+fn stored_position(piece : &FumenPiece) -> (i32, i32){
+    match (piece.kind, piece.rotation) {
+        ('O', Rotation::Left) => (piece.x - 1, piece.y + 1),
+        ('O', Rotation::Reverse) | ('I', Rotation::Reverse) | ('S', Rotation::Right) => (piece.x - 1, piece.y),
+        ('O', Rotation::Spawn) | ('I', Rotation::Left) | ('S', Rotation::Spawn) | ('Z', Rotation::Spawn) => (piece.x, piece.y + 1),
+        ('Z', Rotation::Left) => (piece.x + 1, piece.y),
+        _ => (piece.x, piece.y)
+    }
+}
+
+fn placed_position(kind : char, rotation : Rotation, x : i32, y : i32) -> (i32, i32){
+    match (kind, rotation) {
+        ('O', Rotation::Left) => (x + 1, y - 1),
+        ('O', Rotation::Reverse) | ('I', Rotation::Reverse) | ('S', Rotation::Right) => (x + 1, y),
+        ('O', Rotation::Spawn) | ('I', Rotation::Left) | ('S', Rotation::Spawn) | ('Z', Rotation::Spawn) => (x, y - 1),
+        ('Z', Rotation::Left) => (x - 1, y),
+        _ => (x, y)
+    }
+}
+
+//JavaScript's escape(), which fumen applies to comments
+fn escape(text : &str) -> String{
+    let mut escaped = String::new();
+
+    for unit in text.encode_utf16() {
+        match char::from_u32(unit as u32) {
+            Some(ch) if ch.is_ascii_alphanumeric() || "@*_+-./".contains(ch) => escaped.push(ch),
+            _ if unit < 256 => escaped.push_str(&format!("%{:02X}", unit)),
+            _ => escaped.push_str(&format!("%u{:04X}", unit)),
+        }
+    }
+
+    return escaped;
+}
+
+fn unescape(text : &str) -> String{
+    let mut units : Vec<u16> = vec![];
+    let mut rest = text;
+
+    while let Some(ch) = rest.chars().next() {
+        let code = match rest.strip_prefix("%u") {
+            Some(hex) if hex.len() >= 4 => u16::from_str_radix(&hex[..4], 16).ok().map(|code| (code, 6)),
+            _ => rest.strip_prefix('%').filter(|hex| hex.len() >= 2).and_then(|hex| u16::from_str_radix(&hex[..2], 16).ok()).map(|code| (code, 3)),
+        };
+
+        match code {
+            Some((code, length)) => {
+                units.push(code);
+                rest = &rest[length..];
+            },
+            None => {
+                units.extend(ch.encode_utf16(&mut [0; 2]).iter());
+                rest = &rest[ch.len_utf8()..];
+            }
+        }
+    }
+
+    return String::from_utf16_lossy(&units);
+}
+
+struct Values{
+    digits : Vec<u32>,
+    position : usize,
+}
+
+impl Values {
+    fn is_empty(&self) -> bool {
+        self.position >= self.digits.len()
+    }
+
+    fn poll(&mut self, count : usize) -> Result<u32, String> {
+        if self.position + count > self.digits.len() {
+            return Err(String::from("the data ends in the middle of a page"));
+        }
+
+        let value = self.digits[self.position..self.position + count].iter().rev().fold(0, |value, digit| value * 64 + digit);
+        self.position += count;
+
+        return Ok(value);
+    }
+}
+
+fn push_value(data : &mut Vec<u32>, mut value : u32, count : usize){
+    for _ in 0..count {
+        data.push(value % 64);
+        value /= 64;
+    }
+}
+
+//the cells of the page with its piece drawn in
+fn cells_with_piece(page : &FumenPage) -> Vec<u8>{
+    let mut cells = page.cells.clone();
+
+    if let Some(piece) = &page.piece {
+        for (dx, dy) in piece_blocks(piece.kind, piece.rotation) {
+            if let Some(idx) = cell_index(piece.x + dx, piece.y + dy) {
+                cells[idx] = piece_code(piece.kind);
+            }
+        }
+    }
+
+    return cells;
+}
+
+//places the piece, clears full rows and raises or mirrors the field as the page asks, which gives the field of the next page
+fn apply_page(page : &FumenPage) -> Vec<u8>{
+    if !page.lock {
+        return page.cells.clone();
+    }
+
+    let cells = cells_with_piece(page);
+    let (field, garbage) = cells.split_at(TOP * WIDTH);
+    let mut rows : Vec<&[u8]> = field.chunks(WIDTH).filter(|row| row.contains(&0)).collect();
+    let empty = [0; WIDTH];
+    let cleared = TOP - rows.len();
+    rows.splice(0..0, vec![&empty[..]; cleared]);
+
+    if page.rise {
+        rows.remove(0);
+        rows.push(garbage);
+    }
+
+    let mut next : Vec<u8> = rows.concat();
+
+    if page.mirror {
+        next.chunks_mut(WIDTH).for_each(|row| row.reverse());
+    }
+
+    next.extend(if page.rise { empty } else { garbage.try_into().unwrap() });
+
+    return next;
+}
+
+pub fn decode_fumen(text : &str) -> Result<Vec<FumenPage>, String>{
+    let start = text.find("115@").filter(|idx| text[..*idx].ends_with(['v', 'm', 'd']))
+        .ok_or("not a fumen v115 string, expected it to start with v115@")?;
+    let mut digits : Vec<u32> = vec![];
+
+    for ch in text[start + 4..].chars().filter(|ch| *ch != '?' && !ch.is_whitespace()) {
+        match DIGITS.iter().position(|digit| *digit as char == ch) {
+            Some(digit) => digits.push(digit as u32),
+            None => return Err(format!("unexpected character '{}' in the fumen data", ch))
+        }
+    }
+
+    let mut values = Values{digits, position: 0};
+    let mut pages : Vec<FumenPage> = vec![];
+    let mut previous = vec![0; BLOCKS];
+    let mut repeat = 0;
+    let mut comment = String::new();
+
+    while !values.is_empty() {
+        let mut cells = previous.clone();
+
+        if repeat > 0 {
+            repeat -= 1;
+        }
+        else {
+            let mut idx = 0;
+
+            while idx < BLOCKS {
+                let run = values.poll(2)?;
+                let (diff, count) = ((run as usize / BLOCKS) as i32 - 8, run as usize % BLOCKS + 1);
+
+                if idx + count > BLOCKS || !(-8..=8).contains(&diff) {
+                    return Err(String::from("the field data is broken"));
+                }
+
+                for cell in cells[idx..idx + count].iter_mut() {
+                    *cell = (*cell as i32 + diff).clamp(0, 8) as u8;
+                }
+
+                idx += count;
+
+                if run == UNCHANGED {
+                    repeat = values.poll(1)?;
+                }
+            }
+        }
+
+        let mut action = values.poll(3)?;
+        let code = (action % 8) as usize;
+        action /= 8;
+        let rotation = ROTATIONS[(action % 4) as usize];
+        action /= 4;
+        let position = action as usize % BLOCKS;
+        action /= BLOCKS as u32;
+        let flags : Vec<bool> = (0..5).map(|bit| action >> bit & 1 == 1).collect();
+        let (rise, mirror, colorize, has_comment, lock) = (flags[0], flags[1], flags[2], flags[3], !flags[4]);
+
+        if has_comment {
+            let length = values.poll(2)? as usize;
+            let mut escaped = String::new();
+
+            for _ in 0..length.div_ceil(4) {
+                let mut value = values.poll(5)?;
+
+                for _ in 0..4 {
+                    escaped.push(COMMENT_CHARS.chars().nth((value % COMMENT_BASE) as usize).unwrap_or(' '));
+                    value /= COMMENT_BASE;
+                }
+            }
+
+            comment = unescape(&escaped.chars().take(length).collect::<String>());
+        }
+
+        let piece = Some(code).filter(|code| (1..8).contains(code)).map(|code| {
+            let (x, y) = (position % WIDTH, TOP - 1 - position / WIDTH);
+            let (x, y) = placed_position(PIECES[code], rotation, x as i32, y as i32);
+
+            FumenPiece{kind: PIECES[code], rotation, x, y}
+        });
+        let page = FumenPage{cells, piece, comment: comment.clone(), lock, rise, mirror, colorize};
+
+        previous = apply_page(&page);
+        pages.push(page);
+    }
+
+    if pages.is_empty() {
+        return Err(String::from("the fumen has no pages"));
+    }
+
+    return Ok(pages);
+}
+
+pub fn encode_fumen(pages : &[FumenPage]) -> String{
+    let mut data : Vec<u32> = vec![];
+    let mut previous = vec![0; BLOCKS];
+    let mut repeat_at : Option<usize> = None; //digit counting the pages which repeat the field
+    let mut comment = "";
+
+    for (idx, page) in pages.iter().enumerate() {
+        let mut runs : Vec<u32> = vec![];
+        let mut run : Option<(u32, u32)> = None; //diff and count - 1
+
+        for (cell, before) in page.cells.iter().zip(previous.iter()) {
+            let diff = (*cell as i32 - *before as i32 + 8) as u32;
+
+            run = match run {
+                Some((current, count)) if current == diff => Some((diff, count + 1)),
+                Some((current, count)) => {
+                    runs.push(current * BLOCKS as u32 + count);
+                    Some((diff, 0))
+                },
+                None => Some((diff, 0)),
+            };
+        }
+
+        runs.extend(run.map(|(diff, count)| diff * BLOCKS as u32 + count));
+
+        match repeat_at {
+            _ if runs != [UNCHANGED] => {
+                runs.iter().for_each(|run| push_value(&mut data, *run, 2));
+                repeat_at = None;
+            },
+            Some(at) if data[at] < 63 => data[at] += 1,
+            _ => {
+                push_value(&mut data, UNCHANGED, 2);
+                repeat_at = Some(data.len());
+                push_value(&mut data, 0, 1);
+            }
+        }
+
+        let new_comment = Some(escape(&page.comment)).filter(|_| page.comment != comment && (idx > 0 || !page.comment.is_empty()));
+        let (code, rotation, position) = match &page.piece {
+            Some(piece) => {
+                let (x, y) = stored_position(piece);
+                (piece_code(piece.kind) as u32, piece.rotation, (TOP as i32 - 1 - y) as u32 * WIDTH as u32 + x as u32)
+            },
+            None => (0, Rotation::Reverse, 0),
+        };
+
+        let mut action = 0;
+        for flag in [!page.lock, new_comment.is_some(), page.colorize, page.mirror, page.rise] {
+            action = action * 2 + flag as u32;
+        }
+        action = action * BLOCKS as u32 + position;
+        action = action * 4 + ROTATIONS.iter().position(|other| *other == rotation).unwrap() as u32;
+        action = action * 8 + code;
+        push_value(&mut data, action, 3);
+
+        if let Some(escaped) = &new_comment {
+            let escaped : Vec<char> = escaped.chars().take(MAX_COMMENT).collect();
+            push_value(&mut data, escaped.len() as u32, 2);
+
+            for chunk in escaped.chunks(4) {
+                let value = chunk.iter().rev().fold(0, |value, ch| value * COMMENT_BASE + COMMENT_CHARS.find(*ch).unwrap_or(0) as u32);
+                push_value(&mut data, value, 5);
+            }
+
+            comment = &page.comment;
+        }
+
+        previous = apply_page(page);
+    }
+
+    let digits : Vec<char> = data.iter().map(|digit| DIGITS[*digit as usize] as char).collect();
+    let mut text = String::from("v115@");
+
+    //the tools break the data with a '?' every 47 characters, counting the 5 of the prefix
+    for (idx, digit) in digits.iter().enumerate() {
+        if idx == 42 || (idx > 42 && (idx - 42) % 47 == 0) {
+            text.push('?');
+        }
+
+        text.push(*digit);
+    }
+
+    return text;
+}
+
+//the page cells of a field 10 columns wide, its bottom row on the bottom row of the fumen
+pub fn field_cells(field : &Field) -> Result<Vec<u8>, String>{
+    if field.width as usize != WIDTH + 2 {
+        return Err(format!("fumen fields are {} columns wide, this one has {}", WIDTH, field.width - 2));
+    }
+
+    let mut cells = vec![0; BLOCKS];
+
+    for (row, line) in field_grid(field).iter().rev().enumerate() {
+        for (x, cell) in line.iter().enumerate() {
+            if let Some(kind) = cell {
+                let idx = cell_index(x as i32, row as i32).ok_or(format!("fumen fields are {} rows high, the stack is higher", TOP))?;
+                cells[idx] = piece_code(*kind);
+            }
+        }
+    }
+
+    return Ok(cells);
+}
+
+//the field of a page, `height` rows high; the garbage row below it is left out
+pub fn page_field(cells : &[u8], height : u16) -> Result<Field, String>{
+    let mut field = create_field(WIDTH as u16 + 2, height);
+    let mut taken : Vec<(Point, char)> = vec![];
+
+    for (idx, code) in cells[..TOP * WIDTH].iter().enumerate().filter(|(_, code)| **code != 0) {
+        let (x, row) = ((idx % WIDTH) as i32, (TOP - 1 - idx / WIDTH) as i32);
+
+        if row >= height as i32 {
+            return Err(format!("the fumen field does not fit into {} rows", height));
+        }
+
+        taken.push((Point{x: x + 1, y: height as i32 - 1 - row}, PIECES[*code as usize]));
+    }
+
+    place_cells(&mut field, &taken, 0);
+
+    return Ok(field);
+}
+
+//the fumen piece which covers exactly the given field cells, if the brick is one of the seven tetrominoes
+pub fn find_piece(brick : &DeadBrick, field : &Field) -> Option<FumenPiece>{
+    let cells : Vec<(i32, i32)> = brick.vertices.iter().map(|vertex| (vertex.x - 1, field.height as i32 - 1 - vertex.y)).collect();
+
+    if piece_code(brick.kind) > 7 || cells.len() != 4 {
+        return None;
+    }
+
+    for rotation in ROTATIONS {
+        let blocks = piece_blocks(brick.kind, rotation);
+
+        for (x, y) in cells.iter() { //the centre is one of the cells
+            if blocks.iter().all(|(dx, dy)| cells.contains(&(x + dx, y + dy))) {
+                return Some(FumenPiece{kind: brick.kind, rotation, x: *x, y: *y});
+            }
+        }
+    }
+
+    return None;
+}
+
+//one page per lock, each with the field before the piece was placed, and a last page with the field at the end
+pub fn game_pages(locks : &[(Field, DeadBrick)], field : &Field) -> Result<Vec<FumenPage>, String>{
+    let mut pages : Vec<FumenPage> = vec![];
+
+    for (before, brick) in locks.iter() {
+        let mut page = create_page(field_cells(before)?);
+
+        match find_piece(brick, before) {
+            Some(piece) => page.piece = Some(piece),
+            None => { //drawn into the field of the page, e.g. a pentomino
+                let mut with_brick = before.clone();
+                with_brick.bricks.push(brick.clone());
+                page.cells = field_cells(&with_brick)?;
+            }
+        }
+
+        pages.push(page);
+    }
+
+    pages.push(create_page(field_cells(field)?));
+
+    if let Some(first) = pages.first_mut() {
+        first.colorize = true; //as the tools write the first page
+    }
+
+    return Ok(pages);
+}
+
+//every page as a text board with its piece drawn in, up to the highest taken row, after `#` lines with the page number,
+//the comment and the garbage row
+pub fn render_pages(pages : &[FumenPage]) -> String{
+    let mut text = String::new();
+
+    for (idx, page) in pages.iter().enumerate() {
+        let cells = cells_with_piece(page);
+        let (field, garbage) = cells.split_at(TOP * WIDTH);
+        let rows = field.chunks(WIDTH).position(|row| row.iter().any(|code| *code != 0)).map_or(1, |top| TOP - top);
+
+        text.push_str(&format!("# page {}\n", idx + 1));
+
+        if !page.comment.is_empty() {
+            text.push_str(&format!("# comment: {}\n", page.comment.replace('\n', " ")));
+        }
+
+        if garbage.iter().any(|code| *code != 0) {
+            text.push_str(&format!("# garbage: {}\n", garbage.iter().map(|code| if *code == 0 { EMPTY_CELL } else { PIECES[*code as usize] }).collect::<String>()));
+        }
+
+        let field = page_field(&cells, rows as u16).expect("the taken rows fit");
+        text.push_str(&render_board(&Board{field, active: None, hold: None, queue: vec![]}));
+    }
+
+    return text;
+}
+
+//a page for each text board, with the comment of its `# comment:` line
+pub fn board_pages(texts : &[String]) -> Result<Vec<FumenPage>, String>{
+    let mut pages : Vec<FumenPage> = vec![];
+
+    for (idx, text) in texts.iter().enumerate() {
+        let board = parse_board(text).map_err(|err| format!("board {}: {}", idx + 1, err))?;
+        let mut page = create_page(field_cells(&board.field).map_err(|err| format!("board {}: {}", idx + 1, err))?);
+        page.comment = text.lines().find_map(|line| line.trim().strip_prefix("# comment:")).map_or(String::new(), |comment| String::from(comment.trim()));
+        page.colorize = pages.is_empty();
+        pages.push(page);
+    }
+
+    return Ok(pages);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board_text(field : &Field) -> String {
+        render_board(&Board{field: field.clone(), active: None, hold: None, queue: vec![]})
+    }
+
+    #[test]
+    fn an_empty_field_encodes_as_the_tools_write_it() {
+        let mut page = create_page(vec![0; BLOCKS]);
+        page.colorize = true;
+
+        assert_eq!(encode_fumen(&[page.clone()]), "v115@vhAAgH");
+        assert_eq!(decode_fumen("v115@vhAAgH").unwrap(), vec![page]);
+    }
+
+    #[test]
+    fn a_t_placed_at_the_bottom_shows_on_the_next_page() {
+        let pages = decode_fumen("v115@vhBVQJAAA").unwrap();
+        let piece = pages[0].piece.unwrap();
+
+        assert_eq!(piece, FumenPiece{kind: 'T', rotation: Rotation::Spawn, x: 4, y: 0});
+        assert_eq!(board_text(&page_field(&pages[1].cells, 2).unwrap()), "....T.....\n...TTT....\n");
+    }
+
+    #[test]
+    fn pages_with_pieces_comments_and_garbage_come_back_unchanged() {
+        let board = parse_board("..........\nT.........\nTT.....ZZ.\nT.IIII.OZZ\nGGGGG.GGGG\n").unwrap();
+        let mut first = create_page(field_cells(&board.field).unwrap());
+        first.piece = Some(FumenPiece{kind: 'L', rotation: Rotation::Right, x: 5, y: 1});
+        first.comment = String::from("Tëst ü, 100% 漢字");
+        first.colorize = true;
+
+        let mut second = create_page(apply_page(&first));
+        second.comment = first.comment.clone();
+        second.piece = Some(FumenPiece{kind: 'I', rotation: Rotation::Left, x: 9, y: 2});
+        second.rise = true;
+        second.cells[BLOCKS - 3] = 8; //the garbage row rises below the field
+
+        let mut third = create_page(apply_page(&second));
+        third.comment = String::from("done");
+        third.lock = false;
+        third.piece = Some(FumenPiece{kind: 'O', rotation: Rotation::Spawn, x: 0, y: 5});
+
+        let mut pages = vec![first, second, third];
+        pages.extend(vec![create_page(apply_page(&pages[2])); 70].into_iter().map(|page| FumenPage{comment: String::from("done"), ..page}));
+
+        let fumen = encode_fumen(&pages);
+
+        assert!(fumen.contains('?'));
+        assert_eq!(decode_fumen(&fumen).unwrap(), pages);
+    }
+
+    #[test]
+    fn a_field_comes_back_unchanged_from_a_fumen() {
+        let board = parse_board("..........\n.....S....\nZ...SS....\nZZ..SJJJ..\n.ZGGGGGGJG\n").unwrap();
+        let pages = decode_fumen(&encode_fumen(&[create_page(field_cells(&board.field).unwrap())])).unwrap();
+
+        assert_eq!(board_text(&page_field(&pages[0].cells, 5).unwrap()), board_text(&board.field));
+    }
+
+    #[test]
+    fn malformed_fumens_are_rejected_without_panicking() {
+        for text in ["é115@vhAAgH", "115@vhAAgH", "x115@vhAAgH", "v115@vhAAgé", "v115@vhAAg", "v115@vh", "v115@9gé"] {
+            assert!(decode_fumen(text).is_err(), "{}", text);
+        }
+
+        assert!(decode_fumen("év115@vhAAgH").is_ok());
+    }
+
+    #[test]
+    fn landed_tetrominoes_are_found_in_every_rotation() {
+        let field = create_field(12, 6);
+
+        for kind in ['I', 'T', 'O', 'L', 'J', 'S', 'Z'] {
+            for rotation in ROTATIONS {
+                let mut cells : Vec<(i32, i32)> = piece_blocks(kind, rotation).iter().map(|(dx, dy)| (4 + dx, 2 + dy)).collect();
+                let vertices = cells.iter().map(|(x, y)| Point{x: x + 1, y: 6 - 1 - y}).collect();
+                let found = find_piece(&DeadBrick{vertices, kind, locked_at: 0}, &field).unwrap();
+                let mut covered : Vec<(i32, i32)> = piece_blocks(kind, found.rotation).iter().map(|(dx, dy)| (found.x + dx, found.y + dy)).collect();
+                cells.sort();
+                covered.sort();
+
+                assert_eq!(covered, cells, "{} {:?}", kind, rotation);
+            }
+        }
+    }
+}
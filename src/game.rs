@@ -0,0 +1,840 @@
+use std::sync::Arc;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::brick::{brick_cells, get_width, rotate, Brick, DeadBrick};
+use crate::field::{can_descend_brick, clear_rows, BoardVisibility, ClearGravity, create_field, find_drop_position, find_full_rows, insert_garbage_rows, is_overflowing, land_brick, will_have_collision, Field};
+use crate::input::Action;
+use crate::mode::{create_mode, GameMode, Scoring};
+use crate::pieces::{draw_piece, generate_bag, load_piece_set, PieceSet};
+use crate::point::{translate_by, Point};
+use crate::utility::{get_screen_translated_vertices, shorten_interval};
+use crate::cli::{ModeArgs, ModeName};
+
+use crate::game::State::{BrickGen, Clearing, Descend, Stop, Touchdown};
+
+#[derive(Copy, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum State { //state of the game
+    Touchdown,
+    Clearing, //full rows wait for `line_clear_delay` before being removed
+    BrickGen,
+    Descend,
+    Stop
+}
+
+#[derive(Copy, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Outcome {
+    Playing,
+    Won, //the mode's goal was reached
+    Lost,
+}
+
+#[derive(Copy, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Randomizer {
+    Random, //every brick is drawn independently
+    Bag, //every piece of the set once in a shuffled bag, then the next bag
+}
+
+#[derive(Copy, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RotationSystem {
+    Classic, //the brick turns in place or not at all
+    Kicks, //a blocked rotation is retried one or two columns to the side and one row up
+}
+
+#[derive(Copy, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GravityMode {
+    Classic, //the interval shrinks by the decay factor with every row the brick descends
+    Level, //the interval follows the level, which goes up every 10 cleared rows
+    Fixed, //the interval of the starting level, for good
+}
+
+const KICKS : [Point; 6] = [Point{x: 0, y: 0}, Point{x: -1, y: 0}, Point{x: 1, y: 0}, Point{x: 0, y: -1}, Point{x: -2, y: 0}, Point{x: 2, y: 0}];
+const MAX_LOCK_RESETS : u32 = 15; //moves and rotations on the ground which restart the lock delay
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Input { //what the player does to the game, as recorded for a replay
+    Press(Action),
+    Release(Action),
+    Column(i32), //the pointer moved the brick to this field column
+}
+
+#[derive(Copy, Clone)]
+pub struct Shift{ //a held move key
+    pub(crate) action : Action,
+    pub(crate) next : u64, //game time of the next automatic move
+}
+
+#[derive(Clone)]
+pub enum GameEvent { //what happened during the last update, for the frontend to animate
+    Landed { vertices: Vec<Point> },
+    RowsCleared { rows: Vec<i32> }, //the rows are still on the field when the event is sent
+    Spawned,
+    ToppedOut,
+    GoalReached,
+}
+
+#[derive(Clone)]
+pub struct Game{
+    pub(crate) field : Field,
+    pub(crate) brick : Brick,
+    pub(crate) spawned_brick : Brick, //the active brick as it was spawned, before any rotation
+    pub(crate) held_brick : Option<Brick>,
+    pub(crate) hold_used : bool, //hold can be used once per brick
+    pub(crate) master_node_position : Point, //master node is in the bottom left corner of a rectangle drawn over the brick
+    pub(crate) state : State,
+    pub(crate) mode : Arc<dyn GameMode>,
+    pub(crate) outcome : Outcome,
+    pub(crate) ended_at : Option<u64>, //game time at which the game was won or lost
+    pub(crate) score : u32, //counted the way the mode scores
+    pub(crate) lines : u32, //how many rows were cleared
+    pub(crate) seed : u64,
+    pub(crate) rng : ChaCha12Rng, //every brick comes from here, so a seed reproduces the whole sequence; the generator of rand's StdRng, which can tell its position
+    pub(crate) garbage_rng : ChaCha12Rng, //kept apart so that garbage does not change the brick sequence
+    pub(crate) garbage_hole : Option<i32>, //column of the hole in the last garbage row
+    pub(crate) messiness : f64, //chance that the next garbage row has its hole in another column
+    pub(crate) interval : u64, //how long will it take between each brick descent in ms
+    pub(crate) level : u32,
+    pub(crate) decay : f64, //factor applied to the interval on every descent in the classic gravity mode
+    pub(crate) gravity : GravityMode,
+    pub(crate) clear_gravity : ClearGravity,
+    pub(crate) board : BoardVisibility,
+    pub(crate) fade_ms : u64, //how long a landed brick shows on a fading board
+    pub(crate) randomizer : Randomizer,
+    pub(crate) rotation : RotationSystem,
+    pub(crate) scale : u16, //field cells per side of a brick vertex: 1, or 2 in big mode where bricks move in 2-cell steps
+    pub(crate) pieces : Arc<PieceSet>,
+    pub(crate) bag : Vec<usize>, //what is left of the current bag, as indices into the piece set
+    pub(crate) sequence : Option<Vec<usize>>, //a fixed piece sequence instead of the randomizer, next piece last; the game is lost once it and the hold are empty
+    pub(crate) placed : u32, //bricks locked so far
+    pub(crate) spun : bool, //the last move of the active brick was a rotation
+    pub(crate) t_spin : bool, //the brick which locked last was a T turned into a slot with three of the four corners around its centre taken
+    pub(crate) locks : Option<Vec<(Field, DeadBrick)>>, //when recorded, the field before every lock and the brick which locked
+    pub(crate) inputs : Option<Vec<(u64, Input)>>, //when recorded, every input with the game time it was applied at
+    pub(crate) line_clear_delay : u64, //ms between a row filling up and its removal
+    pub(crate) das : u64, //ms a move key is held before it repeats
+    pub(crate) arr : u64, //ms between repeated moves, 0 slides the brick to the wall
+    pub(crate) lock_delay : u64, //ms a brick may rest on the ground; 0 locks it at the next descent
+    pub(crate) auto_repeat : bool, //the input reports key releases, so the game repeats held moves itself
+    pub(crate) shift : Option<Shift>,
+    pub(crate) lock_deadline : Option<u64>, //set while the brick rests on the ground
+    pub(crate) lock_resets : u32,
+    pub(crate) time : u64, //game time in ms up to which the game has been simulated
+    pub(crate) deadline : u64, //game time of the next descent or row removal
+    pub(crate) events : Vec<GameEvent>,
+}
+
+pub fn create_game(field_width : u16, field_height : u16, seed : u64) -> Game{
+    let mut rng = ChaCha12Rng::seed_from_u64(seed);
+    let pieces = Arc::new(load_piece_set("legacy").unwrap());
+    let brick = draw_piece(&pieces, &mut rng);
+
+    Game{
+        field: create_field(field_width, field_height),
+        spawned_brick: brick.clone(),
+        brick,
+        held_brick: None,
+        hold_used: false,
+        master_node_position: Point{x: ((field_width - 2) / 2) as i32, y: 0},
+        state: BrickGen,
+        mode: create_mode(ModeName::Classic, &ModeArgs::default()),
+        outcome: Outcome::Playing,
+        ended_at: None,
+        score: 0,
+        lines: 0,
+        seed,
+        rng,
+        garbage_rng: ChaCha12Rng::seed_from_u64(seed.wrapping_add(1)),
+        garbage_hole: None,
+        messiness: 0.0,
+        interval: level_interval(1),
+        level: 1,
+        decay: 0.999,
+        gravity: GravityMode::Classic,
+        clear_gravity: ClearGravity::Cascade,
+        board: BoardVisibility::Visible,
+        fade_ms: 0,
+        randomizer: Randomizer::Random,
+        rotation: RotationSystem::Classic,
+        scale: 1,
+        pieces,
+        bag: vec![],
+        sequence: None,
+        placed: 0,
+        spun: false,
+        t_spin: false,
+        locks: None,
+        inputs: None,
+        line_clear_delay: 0,
+        das: 0,
+        arr: 0,
+        lock_delay: 0,
+        auto_repeat: false,
+        shift: None,
+        lock_deadline: None,
+        lock_resets: 0,
+        time: 0,
+        deadline: 0,
+        events: vec![],
+    }
+}
+
+//gravity of the guideline: (0.8 - (level - 1) * 0.007) ^ (level - 1) seconds per row
+pub fn level_interval(level : u32) -> u64{
+    let level = level.clamp(1, 20) as f64 - 1.0;
+    return ((0.8 - level * 0.007).powf(level) * 1000.0).round() as u64;
+}
+
+//advances the game to `now` (ms of game time); returns whether anything changed
+pub fn update_game(game : &mut Game, now : u64) -> bool{
+    if now < game.time { //events without a time of their own are applied at the current game time
+        return false;
+    }
+
+    let time_limit = game.mode.time_limit();
+    let mut changed = advance(game, time_limit.map_or(now, |limit| now.min(limit)));
+
+    if let Some(limit) = time_limit {
+        if now >= limit && game.state != Stop {
+            end_game(game, Outcome::Won);
+            changed = true;
+        }
+    }
+
+    game.time = now;
+
+    return changed;
+}
+
+fn advance(game : &mut Game, now : u64) -> bool{
+    let mut changed = false;
+
+    loop {
+        match game.state {
+            Touchdown => {
+                game.t_spin = is_t_spin(game);
+                game.placed += 1;
+
+                if let Some(locks) = game.locks.as_mut() {
+                    locks.push((game.field.clone(), DeadBrick{vertices: brick_cells(&game.brick, &game.master_node_position), kind: game.brick.kind, locked_at: game.time}));
+                }
+
+                land_brick(&game.brick, &game.master_node_position, &mut game.field, game.time);
+                let vertices = brick_cells(&game.brick, &game.master_node_position);
+                game.events.push(GameEvent::Landed { vertices });
+
+                start_clearing(game);
+            },
+
+            Clearing => {
+                if now < game.deadline {
+                    break;
+                }
+
+                game.time = game.deadline;
+                let full_rows = find_full_rows(&game.field);
+                clear_rows(&mut game.field, &full_rows, game.clear_gravity);
+
+                start_clearing(game); //falling fragments may have completed more rows
+            },
+
+            BrickGen => {
+                if game.sequence.as_ref().is_some_and(|sequence| sequence.is_empty()) && game.held_brick.is_none() {
+                    end_game(game, Outcome::Lost); //out of pieces before the goal was reached
+                }
+                else {
+                    let brick = next_brick(game);
+                    spawn_brick(game, brick);
+                    game.hold_used = false;
+                }
+            },
+
+            Descend => {
+                let due = next_deadline(game);
+
+                if now < due {
+                    break;
+                }
+
+                game.time = due;
+
+                if game.lock_deadline == Some(due) {
+                    game.state = Touchdown;
+                }
+                else if game.shift.is_some_and(|shift| shift.next == due) {
+                    if !auto_shift(game) {
+                        continue;
+                    }
+                }
+                else {
+                    descend(game);
+                }
+            },
+
+            Stop => break
+        }
+
+        changed = true;
+    }
+
+    game.time = now;
+
+    return changed;
+}
+
+fn next_deadline(game : &Game) -> u64{
+    let mut due = game.deadline;
+
+    if let Some(shift) = &game.shift {
+        due = due.min(shift.next);
+    }
+
+    if let Some(lock) = game.lock_deadline {
+        due = due.min(lock);
+    }
+
+    return due;
+}
+
+fn descend(game : &mut Game){
+    if game.mode.scoring() == Scoring::Survival {
+        game.score += 1;
+    }
+
+    if game.gravity == GravityMode::Classic {
+        game.interval = shorten_interval(game.interval, game.decay);
+    }
+
+    if can_descend_brick(&game.brick, &game.master_node_position, &game.field) {
+        translate_by(&mut game.master_node_position, &Point { x: 0, y: 1 });
+        game.spun = false;
+    }
+    else if game.lock_delay == 0 {
+        game.state = Touchdown;
+        return;
+    }
+
+    update_lock(game, false);
+    game.deadline += game.interval;
+}
+
+//repeats the held move; returns whether the brick moved
+fn auto_shift(game : &mut Game) -> bool{
+    let shift = game.shift.unwrap();
+    let step = if shift.action == Action::MoveLeft { move_brick_left } else { move_brick_right };
+    let moved = step(game);
+
+    if game.arr == 0 {
+        while step(game) {}
+    }
+
+    game.shift = Some(Shift{next: shift.next + game.arr.max(1), ..shift});
+
+    return moved;
+}
+
+//starts the lock delay once the brick rests on the ground and drops it when the brick can fall again;
+//moves on the ground restart the delay a limited number of times
+fn update_lock(game : &mut Game, moved : bool){
+    if game.lock_delay == 0 {
+        return;
+    }
+
+    if can_descend_brick(&game.brick, &game.master_node_position, &game.field) {
+        game.lock_deadline = None;
+    }
+    else if game.lock_deadline.is_none() {
+        game.lock_deadline = Some(game.time + game.lock_delay);
+    }
+    else if moved && game.lock_resets < MAX_LOCK_RESETS {
+        game.lock_resets += 1;
+        game.lock_deadline = Some(game.time + game.lock_delay);
+    }
+}
+
+fn add_lines(game : &mut Game, lines : u32){
+    let levels_before = game.lines / 10;
+    game.lines += lines;
+
+    if game.mode.scoring() == Scoring::Guideline {
+        game.score += [0, 100, 300, 500, 800][lines.min(4) as usize] * game.level;
+    }
+
+    if game.gravity == GravityMode::Level {
+        game.level += game.lines / 10 - levels_before;
+        game.interval = level_interval(game.level);
+    }
+}
+
+fn next_brick(game : &mut Game) -> Brick{
+    if let Some(sequence) = &mut game.sequence {
+        return match sequence.pop() {
+            Some(idx) => game.pieces.bricks[idx].clone(),
+            None => game.held_brick.take().expect("a brick is only taken while there is one left"), //the held brick comes last
+        };
+    }
+
+    match game.randomizer {
+        Randomizer::Random => draw_piece(&game.pieces, &mut game.rng),
+        Randomizer::Bag => {
+            if game.bag.is_empty() {
+                game.bag = generate_bag(&game.pieces, &mut game.rng);
+            }
+
+            game.pieces.bricks[game.bag.pop().unwrap()].clone()
+        }
+    }
+}
+
+fn spawn_brick(game : &mut Game, mut brick : Brick){
+    brick.scale = game.scale;
+    let scale = game.scale as i32;
+    //bricks spawn where the last one was, so its spawn offset is taken back first
+    game.master_node_position.x += (brick.spawn_offset.x - game.brick.spawn_offset.x) * scale;
+    game.spawned_brick = brick.clone();
+    game.brick = brick;
+    game.lock_deadline = None;
+    game.lock_resets = 0;
+    game.spun = false;
+    reset_master_node(&game.brick, &mut game.master_node_position, &game.field);
+
+    if let Some(shift) = &mut game.shift { //a move held through the spawn repeats right away
+        shift.next = shift.next.max(game.time);
+    }
+
+    let mode = game.mode.clone();
+    let fits = can_descend_brick(&game.brick, &game.master_node_position, &game.field)
+        || (mode.top_out(game) && can_descend_brick(&game.brick, &game.master_node_position, &game.field));
+
+    if fits {
+        game.state = Descend;
+        game.deadline = game.time + game.interval;
+        game.events.push(GameEvent::Spawned);
+    }
+    else {
+        top_out(game);
+    }
+}
+
+fn top_out(game : &mut Game){
+    game.events.push(GameEvent::ToppedOut);
+    end_game(game, Outcome::Lost);
+}
+
+//adds garbage rows at the bottom, pushing the stack and, if needed, the active brick up;
+//in big mode every row and every hole is as big as a brick vertex
+pub fn add_garbage(game : &mut Game, rows : u16){
+    let scale = game.scale as i32;
+    let columns = (game.field.width as i32 - 2) / scale;
+    let mut holes : Vec<i32> = vec![];
+
+    for _ in 0..rows {
+        let hole = match game.garbage_hole {
+            Some(hole) if !game.garbage_rng.random_bool(game.messiness) => hole,
+            _ => 1 + (game.garbage_rng.random_range(1..=columns) - 1) * scale
+        };
+
+        game.garbage_hole = Some(hole);
+        holes.extend(vec![hole; scale as usize]);
+    }
+
+    insert_garbage_rows(&mut game.field, &holes, scale, game.time);
+
+    if game.state == Descend {
+        while will_have_collision(&brick_cells(&game.brick, &game.master_node_position), &game.field) {
+            translate_by(&mut game.master_node_position, &Point { x: 0, y: -1 });
+        }
+
+        update_lock(game, false);
+    }
+
+    let mode = game.mode.clone();
+    let fits = !is_overflowing(&game.field)
+        || (mode.top_out(game) && !is_overflowing(&game.field));
+
+    if !fits {
+        top_out(game);
+    }
+}
+
+//whether a landed brick shows on the board, which depends on when it locked; everything shows once the game is over
+pub fn is_brick_visible(game : &Game, brick : &DeadBrick) -> bool{
+    match game.board {
+        _ if game.state == Stop => true,
+        BoardVisibility::Visible => true,
+        BoardVisibility::Fading => game.time < brick.locked_at + game.fade_ms,
+        BoardVisibility::Invisible => false,
+    }
+}
+
+fn end_game(game : &mut Game, outcome : Outcome){
+    game.state = Stop;
+    game.outcome = outcome;
+    game.ended_at = Some(game.time);
+
+    if outcome == Outcome::Won {
+        game.events.push(GameEvent::GoalReached);
+    }
+}
+
+fn start_clearing(game : &mut Game){
+    let full_rows = find_full_rows(&game.field);
+
+    if full_rows.is_empty() {
+        game.state = BrickGen;
+    }
+    else {
+        add_lines(game, (full_rows.len() as u32).div_ceil(game.scale as u32)); //counted right away, so a goal is reached on the lock and not after the animation
+        game.events.push(GameEvent::RowsCleared { rows: full_rows });
+        game.state = Clearing;
+        game.deadline = game.time + game.line_clear_delay;
+    }
+
+    let mode = game.mode.clone();
+    if mode.is_won(game) {
+        end_game(game, Outcome::Won);
+    }
+}
+
+//the three corner rule: a T whose last move was a rotation and which has three of the four cells diagonal to its centre
+//taken, by bricks or by the walls and the floor
+fn is_t_spin(game : &Game) -> bool{
+    let brick = &game.brick;
+
+    if brick.kind != 'T' || !game.spun || brick.centre.x % 2 != 0 || brick.centre.y % 2 != 0 {
+        return false;
+    }
+
+    let scale = brick.scale as i32;
+    let corners : Vec<Point> = [(-1, -1), (1, -1), (-1, 1), (1, 1)].iter()
+        .map(|(dx, dy)| Point{x: (brick.centre.x / 2 + dx) * scale, y: (brick.centre.y / 2 + dy) * scale})
+        .collect();
+    let taken = get_screen_translated_vertices(&corners, &game.master_node_position).iter()
+        .filter(|corner| corner.x < 1 || corner.x > game.field.width as i32 - 2 || corner.y >= game.field.height as i32
+            || will_have_collision(&[**corner], &game.field))
+        .count();
+
+    return taken >= 3;
+}
+
+//rotates clockwise by the given number of quarter turns: 1 for clockwise, 2 for 180, 3 for counter-clockwise
+pub fn rotate_brick(game : &mut Game, quarter_turns : u8) -> bool {
+    if game.state != Descend {
+        return false;
+    }
+
+    let scale = game.scale as i32;
+    let mut requested_brick = game.brick.clone();
+    let mut turned_mn_pos = game.master_node_position;
+    for _ in 0..quarter_turns {
+        let shift = rotate(&mut requested_brick);
+        translate_by(&mut turned_mn_pos, &Point { x: shift.x * scale, y: shift.y * scale });
+    }
+
+    let future_width = get_width(&requested_brick) as i32;
+    let kicks : Vec<Point> = match (game.rotation, &game.brick.kicks) {
+        (RotationSystem::Classic, _) => KICKS[..1].to_vec(),
+        (RotationSystem::Kicks, Some(kicks)) => KICKS[..1].iter().chain(kicks.iter()).copied().collect(), //in place first
+        (RotationSystem::Kicks, None) => KICKS.to_vec(),
+    };
+
+    for kick in kicks.iter() {
+        let mut kicked_mn_pos = turned_mn_pos;
+        translate_by(&mut kicked_mn_pos, &Point { x: kick.x * scale, y: kick.y * scale });
+        let future_vertices = brick_cells(&requested_brick, &kicked_mn_pos);
+        let fits_in_field = kicked_mn_pos.x > 0 && kicked_mn_pos.x + future_width < (game.field.width as i32)
+            && future_vertices.iter().all(|vertex| vertex.y < game.field.height as i32); //kicks may go down
+
+        let has_collision = will_have_collision(&future_vertices, &game.field);
+
+        if fits_in_field && (!has_collision) {
+            game.brick = requested_brick;
+            game.master_node_position = kicked_mn_pos;
+            game.spun = true;
+            update_lock(game, true);
+            return true;
+        }
+    }
+
+    return false;
+}
+
+pub fn move_brick_right(game : &mut Game) -> bool {
+    if game.state != Descend {
+        return false;
+    }
+
+    let step = Point { x: game.brick.scale as i32, y: 0 };
+    let mut future_mn_pos = game.master_node_position;
+    translate_by(&mut future_mn_pos, &step);
+    let width = get_width(&game.brick) as i32;
+    let fits_in_field = future_mn_pos.x + width < (game.field.width as i32);
+
+    let future_vertices = brick_cells(&game.brick, &future_mn_pos);
+
+    if fits_in_field && (!will_have_collision(&future_vertices, &game.field)){
+        translate_by(&mut game.master_node_position, &step);
+        game.spun = false;
+        update_lock(game, true);
+        return true;
+    }
+
+    return false;
+}
+
+pub fn move_brick_left(game : &mut Game) -> bool {
+    if game.state != Descend {
+        return false;
+    }
+
+    let step = Point { x: -(game.brick.scale as i32), y: 0 };
+    let mut future_mn_pos = game.master_node_position;
+    translate_by(&mut future_mn_pos, &step);
+    let fits_in_field = future_mn_pos.x > 0;
+
+    let future_vertices = brick_cells(&game.brick, &future_mn_pos);
+
+    if fits_in_field && (!will_have_collision(&future_vertices, &game.field)){
+        translate_by(&mut game.master_node_position, &step);
+        game.spun = false;
+        update_lock(game, true);
+        return true;
+    }
+
+    return false;
+}
+
+//applies an action which steers the brick; the remaining actions are up to the frontend
+pub fn apply_action(game : &mut Game, action : Action) -> bool {
+    match action {
+        Action::MoveLeft => move_brick_left(game),
+        Action::MoveRight => move_brick_right(game),
+        Action::SoftDrop => soft_drop(game),
+        Action::HardDrop => hard_drop(game),
+        Action::RotateCw => rotate_brick(game, 1),
+        Action::Rotate180 => rotate_brick(game, 2),
+        Action::RotateCcw => rotate_brick(game, 3),
+        Action::Hold => hold_brick(game),
+        Action::Pause | Action::Restart | Action::Quit => false
+    }
+}
+
+fn record_input(game : &mut Game, input : Input) {
+    let time = game.time;
+
+    if let Some(inputs) = game.inputs.as_mut() {
+        inputs.push((time, input));
+    }
+}
+
+//applies a recorded input, the way the frontend applied it
+pub fn apply_input(game : &mut Game, input : Input) -> bool {
+    match input {
+        Input::Press(action) => press_action(game, action),
+        Input::Release(action) => {
+            release_action(game, action);
+            false
+        },
+        Input::Column(column) => move_brick_to_column(game, column),
+    }
+}
+
+//applies a key press; with auto-repeat a held move key keeps moving the brick until it is released
+pub fn press_action(game : &mut Game, action : Action) -> bool {
+    record_input(game, Input::Press(action));
+    let is_move = action == Action::MoveLeft || action == Action::MoveRight;
+
+    if is_move && game.shift.is_some_and(|shift| shift.action == action) {
+        return false; //the terminal repeats held keys as well
+    }
+
+    let moved = apply_action(game, action);
+
+    if is_move && game.auto_repeat {
+        game.shift = Some(Shift{action, next: game.time + game.das});
+    }
+
+    return moved;
+}
+
+pub fn release_action(game : &mut Game, action : Action) {
+    record_input(game, Input::Release(action));
+
+    if game.shift.is_some_and(|shift| shift.action == action) {
+        game.shift = None;
+    }
+}
+
+//slides the brick step by step until it is centered on the given field column or blocked on the way
+pub fn move_brick_to_column(game : &mut Game, column : i32) -> bool {
+    record_input(game, Input::Column(column));
+    let target_x = column - (get_width(&game.brick) as i32 - 1) / 2;
+    let mut moved = false;
+
+    while game.master_node_position.x < target_x && move_brick_right(game) {
+        moved = true;
+    }
+
+    while game.master_node_position.x > target_x && move_brick_left(game) {
+        moved = true;
+    }
+
+    return moved;
+}
+
+pub fn soft_drop(game : &mut Game) -> bool {
+    if game.state != Descend || !can_descend_brick(&game.brick, &game.master_node_position, &game.field) {
+        return false;
+    }
+
+    translate_by(&mut game.master_node_position, &Point { x: 0, y: 1 });
+    game.spun = false;
+    game.deadline = game.time + game.interval; //gravity starts counting anew from the new row
+    update_lock(game, false);
+
+    if game.mode.scoring() == Scoring::Guideline {
+        game.score += 1;
+    }
+
+    return true;
+}
+
+pub fn hard_drop(game : &mut Game) -> bool {
+    if game.state != Descend {
+        return false;
+    }
+
+    let drop_position = find_drop_position(&game.brick, &game.master_node_position, &game.field);
+
+    if game.mode.scoring() == Scoring::Guideline {
+        game.score += 2 * (drop_position.y - game.master_node_position.y) as u32;
+    }
+
+    if drop_position.y > game.master_node_position.y {
+        game.spun = false;
+    }
+
+    game.master_node_position = drop_position;
+    game.state = Touchdown;
+
+    return true;
+}
+
+pub fn hold_brick(game : &mut Game) -> bool {
+    if game.state != Descend || game.hold_used {
+        return false;
+    }
+
+    if game.held_brick.is_none() && game.sequence.as_ref().is_some_and(|sequence| sequence.is_empty()) {
+        return false; //nothing left to take the place of the held brick
+    }
+
+    let next_brick = match game.held_brick.take() {
+        Some(held) => held,
+        None => next_brick(game)
+    };
+
+    game.held_brick = Some(game.spawned_brick.clone());
+    spawn_brick(game, next_brick);
+    game.hold_used = true;
+
+    return true;
+}
+
+fn reset_master_node(brick: &Brick, master_node_position : &mut Point, field: &Field){
+    master_node_position.y = -brick.spawn_offset.y * brick.scale as i32;
+
+    if master_node_position.x + get_width(brick) as i32 >= field.width as i32 {
+        master_node_position.x = field.width as i32 - get_width(brick) as i32 - 1;
+    }
+
+    master_node_position.x = master_node_position.x.max(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::play::new_game;
+
+    fn game(big : bool) -> Game{
+        let mut config = Config::default();
+        config.rules.width = 10;
+        config.rules.height = 20;
+        config.rules.pieces = String::from("tetrominoes");
+        config.rules.big = big;
+        let mut game = new_game(&config, Some(3), create_mode(ModeName::Classic, &ModeArgs::default()));
+        update_game(&mut game, 0);
+
+        return game;
+    }
+
+    //leftmost and rightmost field column of the falling brick
+    fn brick_columns(game : &Game) -> (i32, i32){
+        let cells = brick_cells(&game.brick, &game.master_node_position);
+
+        return (cells.iter().map(|cell| cell.x).min().unwrap(), cells.iter().map(|cell| cell.x).max().unwrap());
+    }
+
+    //a column of landed cells from the top of the field to its bottom
+    fn wall(game : &mut Game, columns : &[i32]){
+        let vertices = columns.iter().flat_map(|x| (0..game.field.height as i32).map(move |y| Point{x: *x, y})).collect();
+        game.field.bricks.push(DeadBrick{vertices, kind: 'G', locked_at: 0});
+    }
+
+    #[test]
+    fn the_brick_follows_the_pointer_up_to_the_walls() {
+        let mut game = game(false);
+        let right = game.field.width as i32 - 2;
+
+        assert!(move_brick_to_column(&mut game, -40));
+        assert_eq!(brick_columns(&game).0, 1);
+        assert!(!move_brick_to_column(&mut game, 0)); //already against the wall
+
+        assert!(move_brick_to_column(&mut game, 99));
+        assert_eq!(brick_columns(&game).1, right);
+
+        move_brick_to_column(&mut game, 5);
+        let (left, right) = brick_columns(&game);
+        assert!(left <= 5 && 5 <= right);
+    }
+
+    #[test]
+    fn the_brick_stops_at_landed_bricks_on_the_way() {
+        let mut game = game(false);
+        wall(&mut game, &[2, 9]);
+
+        move_brick_to_column(&mut game, -40);
+        assert_eq!(brick_columns(&game).0, 3);
+
+        move_brick_to_column(&mut game, 99);
+        assert_eq!(brick_columns(&game).1, 8);
+    }
+
+    #[test]
+    fn a_big_brick_moves_in_steps_of_its_scale() {
+        let mut game = game(true);
+        let right = game.field.width as i32 - 2;
+        let start = game.master_node_position.x;
+
+        for column in [-40, 99, 6, 7, 13] {
+            move_brick_to_column(&mut game, column);
+            assert_eq!((game.master_node_position.x - start) % 2, 0, "column {}", column);
+        }
+
+        move_brick_to_column(&mut game, -40);
+        assert_eq!(brick_columns(&game).0, 1);
+        move_brick_to_column(&mut game, 99);
+        assert_eq!(brick_columns(&game).1, right);
+
+        move_brick_to_column(&mut game, -40);
+        wall(&mut game, &[15, 16]);
+        move_brick_to_column(&mut game, 99);
+        assert_eq!(brick_columns(&game).1, 14); //a big cell left of the wall
+    }
+}
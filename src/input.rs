@@ -0,0 +1,305 @@
+use std::collections::HashMap;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    SoftDrop,
+    HardDrop,
+    RotateCw,
+    RotateCcw,
+    Rotate180,
+    Hold,
+    Pause,
+    Restart,
+    Quit,
+}
+
+pub const ACTIONS : [(&str, Action); 11] = [
+    ("move_left", Action::MoveLeft),
+    ("move_right", Action::MoveRight),
+    ("soft_drop", Action::SoftDrop),
+    ("hard_drop", Action::HardDrop),
+    ("rotate_cw", Action::RotateCw),
+    ("rotate_ccw", Action::RotateCcw),
+    ("rotate_180", Action::Rotate180),
+    ("hold", Action::Hold),
+    ("pause", Action::Pause),
+    ("restart", Action::Restart),
+    ("quit", Action::Quit),
+];
+
+pub const PRESET_NAMES : [&str; 3] = ["arrows", "wasd", "vim"];
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct KeyBinding{
+    pub(crate) code : KeyCode,
+    pub(crate) modifiers : KeyModifiers,
+}
+
+pub type Bindings = HashMap<KeyBinding, Action>;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum KeyState {
+    Pressed,
+    Released,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct ActionEvent{
+    pub(crate) action : Action,
+    pub(crate) state : KeyState,
+    pub(crate) time : u64, //game time in ms at which the action happened
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum InputEvent {
+    Action(ActionEvent),
+    PointerMoved { column: u16 }, //terminal column under the mouse pointer
+    Resize,
+    FocusLost,
+    Other, //any key without a binding, used to resume a paused game
+    Closed, //the source will not produce any more events
+}
+
+pub fn pressed(action : Action, time : u64) -> InputEvent{
+    InputEvent::Action(ActionEvent{action, state: KeyState::Pressed, time})
+}
+
+pub fn released(action : Action, time : u64) -> InputEvent{
+    InputEvent::Action(ActionEvent{action, state: KeyState::Released, time})
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct KeySettings{
+    pub(crate) preset : String,
+    #[serde(flatten)]
+    pub(crate) actions : HashMap<String, Vec<String>>, //action name -> keys, replaces the preset's keys for that action
+}
+
+impl Default for KeySettings {
+    fn default() -> Self {
+        KeySettings{preset: String::from("arrows"), actions: HashMap::new()}
+    }
+}
+
+fn preset_keys(preset : &str) -> Option<Vec<(Action, &'static str)>>{
+    let mut keys = match preset {
+        "arrows" => vec![
+            (Action::MoveLeft, "Left"),
+            (Action::MoveRight, "Right"),
+            (Action::SoftDrop, "Down"),
+            (Action::HardDrop, "Space"),
+            (Action::RotateCw, "Up"),
+            (Action::RotateCw, "x"),
+            (Action::RotateCcw, "z"),
+            (Action::Rotate180, "a"),
+            (Action::Hold, "c"),
+            (Action::Hold, "Shift+c"),
+        ],
+        "wasd" => vec![
+            (Action::MoveLeft, "a"),
+            (Action::MoveRight, "d"),
+            (Action::SoftDrop, "s"),
+            (Action::HardDrop, "Space"),
+            (Action::RotateCw, "w"),
+            (Action::RotateCw, "Right"),
+            (Action::RotateCcw, "Left"),
+            (Action::Rotate180, "Up"),
+            (Action::Hold, "Shift+Left"),
+            (Action::Hold, "e"),
+        ],
+        "vim" => vec![
+            (Action::MoveLeft, "h"),
+            (Action::MoveRight, "l"),
+            (Action::SoftDrop, "j"),
+            (Action::HardDrop, "Space"),
+            (Action::RotateCw, "k"),
+            (Action::RotateCcw, "u"),
+            (Action::Rotate180, "i"),
+            (Action::Hold, "y"),
+        ],
+        _ => return None
+    };
+
+    keys.extend([
+        (Action::Pause, "Esc"),
+        (Action::Pause, "p"),
+        (Action::Restart, "r"),
+        (Action::Quit, "Ctrl+c"),
+        (Action::Quit, "q"),
+    ]);
+
+    return Some(keys);
+}
+
+pub fn find_action(name : &str) -> Option<Action>{
+    ACTIONS.iter().find(|(action_name, _)| *action_name == name).map(|(_, action)| *action)
+}
+
+//parses "Left", "Space", "F5", "x", "Ctrl+c", "Alt+Shift+Up", ...
+pub fn parse_key(text : &str) -> Result<KeyBinding, String>{
+    let mut parts : Vec<&str> = text.split('+').collect();
+
+    if text.ends_with("++") || text == "+" { //the plus key itself
+        parts.truncate(parts.len().saturating_sub(2));
+        parts.push("+");
+    }
+
+    let key = parts.pop().unwrap_or("");
+    let mut modifiers = KeyModifiers::NONE;
+
+    for modifier in parts.iter() {
+        modifiers |= match modifier.to_lowercase().as_str() {
+            "ctrl" | "control" => KeyModifiers::CONTROL,
+            "alt" => KeyModifiers::ALT,
+            "shift" => KeyModifiers::SHIFT,
+            _ => return Err(format!("unknown modifier '{}' in '{}'", modifier, text))
+        };
+    }
+
+    let mut chars = key.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(c), None) => KeyCode::Char(c.to_ascii_lowercase()),
+        _ => match key.to_lowercase().as_str() {
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "space" => KeyCode::Char(' '),
+            "enter" | "return" => KeyCode::Enter,
+            "esc" | "escape" => KeyCode::Esc,
+            "tab" => KeyCode::Tab,
+            "backspace" => KeyCode::Backspace,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            "insert" => KeyCode::Insert,
+            "delete" => KeyCode::Delete,
+            name => match name.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+                Some(n) if (1..=12).contains(&n) => KeyCode::F(n),
+                _ => return Err(format!("unknown key '{}'", text))
+            }
+        }
+    };
+
+    return Ok(KeyBinding{code, modifiers});
+}
+
+//letters are matched case-insensitively, with shift as an explicit modifier
+fn normalize(code : KeyCode, modifiers : KeyModifiers) -> KeyBinding{
+    match code {
+        KeyCode::Char(c) if c.is_ascii_uppercase() => KeyBinding{code: KeyCode::Char(c.to_ascii_lowercase()), modifiers: modifiers | KeyModifiers::SHIFT},
+        KeyCode::BackTab => KeyBinding{code: KeyCode::Tab, modifiers: modifiers | KeyModifiers::SHIFT},
+        _ => KeyBinding{code, modifiers}
+    }
+}
+
+pub fn build_bindings(settings : &KeySettings) -> Result<Bindings, String>{
+    let preset = match preset_keys(&settings.preset) {
+        Some(preset) => preset,
+        None => return Err(format!("keys.preset: unknown preset '{}', expected one of: {}", settings.preset, PRESET_NAMES.join(", ")))
+    };
+
+    let mut bindings = Bindings::new();
+
+    for (action, key) in preset.iter() {
+        if !settings.actions.contains_key(action_name(*action)) {
+            let binding = parse_key(key).expect("preset keys are valid");
+            bindings.insert(normalize(binding.code, binding.modifiers), *action);
+        }
+    }
+
+    let mut configured = Bindings::new(); //the keys of the settings alone, a key may only go to one action
+    let mut names : Vec<&String> = settings.actions.keys().collect();
+    names.sort(); //the same error every run, whatever the order of the map
+
+    for name in names {
+        let action = match find_action(name) {
+            Some(action) => action,
+            None => return Err(format!("keys.{}: unknown action", name))
+        };
+
+        for key in settings.actions[name].iter() {
+            let binding = parse_key(key).map_err(|err| format!("keys.{}: {}", name, err))?;
+            let binding = normalize(binding.code, binding.modifiers);
+
+            if let Some(other) = configured.insert(binding, action).filter(|other| *other != action) {
+                return Err(format!("keys.{}: '{}' is already bound to {}", name, key, action_name(other)));
+            }
+
+            bindings.insert(binding, action);
+        }
+    }
+
+    return Ok(bindings);
+}
+
+pub fn action_name(action : Action) -> &'static str{
+    ACTIONS.iter().find(|(_, known)| *known == action).map(|(name, _)| *name).unwrap()
+}
+
+//the inverse of parse_key, for showing bindings to the player
+pub fn describe_key(binding : &KeyBinding) -> String{
+    let mut text = String::new();
+
+    for (modifier, name) in [(KeyModifiers::CONTROL, "Ctrl+"), (KeyModifiers::ALT, "Alt+"), (KeyModifiers::SHIFT, "Shift+")] {
+        if binding.modifiers.contains(modifier) {
+            text.push_str(name);
+        }
+    }
+
+    match binding.code {
+        KeyCode::Char(' ') => text.push_str("Space"),
+        KeyCode::Char(c) => text.push(c),
+        KeyCode::F(n) => text.push_str(format!("F{}", n).as_str()),
+        KeyCode::Esc => text.push_str("Esc"),
+        KeyCode::PageUp => text.push_str("PageUp"),
+        KeyCode::PageDown => text.push_str("PageDown"),
+        code => text.push_str(format!("{:?}", code).as_str()), //Left, Enter, Tab, Home, ...
+    }
+
+    return text;
+}
+
+//a key bound to the action, preferring plain keys and then alphabetical order so that the choice is stable
+pub fn first_key(bindings : &Bindings, action : Action) -> Option<String>{
+    bindings.iter()
+        .filter(|(_, bound)| **bound == action)
+        .map(|(binding, _)| (!binding.modifiers.is_empty(), describe_key(binding)))
+        .min()
+        .map(|(_, name)| name)
+}
+
+pub fn find_bound_action(bindings : &Bindings, key : &KeyEvent) -> Option<Action>{
+    bindings.get(&normalize(key.code, key.modifiers)).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(actions : &[(&str, &[&str])]) -> KeySettings{
+        KeySettings{
+            preset: String::from("arrows"),
+            actions: actions.iter().map(|(name, keys)| (String::from(*name), keys.iter().map(|key| String::from(*key)).collect())).collect(),
+        }
+    }
+
+    #[test]
+    fn a_key_configured_for_two_actions_is_rejected() {
+        let space = KeyBinding{code: KeyCode::Char(' '), modifiers: KeyModifiers::NONE};
+
+        for _ in 0..20 { //the map iterates in another order every time
+            assert_eq!(build_bindings(&settings(&[("rotate_cw", &["Up", "Space"]), ("hard_drop", &["space"])])).err(), Some(String::from("keys.rotate_cw: 'Space' is already bound to hard_drop")));
+        }
+
+        //a configured key still takes over the preset key of another action, and listing a key twice is harmless
+        let bindings = build_bindings(&settings(&[("rotate_cw", &["Space", "Space"])])).unwrap();
+        assert_eq!(bindings[&space], Action::RotateCw);
+    }
+}
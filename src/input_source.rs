@@ -0,0 +1,300 @@
+use std::sync::mpsc;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+use crossterm::event::{read, Event, KeyEventKind, MouseButton, MouseEventKind};
+
+use crate::input::{find_bound_action, pressed, released, Action, Bindings, InputEvent};
+
+pub trait InputSource {
+    //returns the next event due at game time `now`, waiting at most `timeout` for one to arrive
+    fn poll(&mut self, now : u64, timeout : Duration) -> Option<InputEvent>;
+}
+
+//reads the keyboard (and the mouse, once captured) through crossterm on a separate thread
+pub struct TerminalInput{
+    input : ChannelInput, //fed by the reader
+}
+
+pub fn create_terminal_input(bindings : Bindings) -> TerminalInput{
+    let (sender, input) = create_channel_input();
+    spawn_terminal_reader(bindings, sender);
+
+    return TerminalInput{input};
+}
+
+//forwards terminal events to the given channel, e.g. to share it with a bot
+pub fn spawn_terminal_reader(bindings : Bindings, sender : InputSender){
+    thread::spawn(move || read_terminal(&bindings, |event| send_input(&sender, event)));
+}
+
+//hands every event to `send` until it returns false
+fn read_terminal(bindings : &Bindings, send : impl Fn(InputEvent) -> bool){
+    loop {
+        let event = match terminal_event(bindings, read().unwrap()) {
+            Some(event) => event,
+            None => continue
+        };
+
+        if !send(event) {
+            break;
+        }
+    }
+}
+
+//what a crossterm event means to the game, None for those it ignores; the time is set by the source
+fn terminal_event(bindings : &Bindings, event : Event) -> Option<InputEvent>{
+    let event = match event {
+        Event::Key(key) => match (find_bound_action(bindings, &key), key.kind) {
+            (Some(action), KeyEventKind::Press) | (Some(action), KeyEventKind::Repeat) => pressed(action, 0),
+            (Some(action), KeyEventKind::Release) => released(action, 0),
+            (None, KeyEventKind::Press) => InputEvent::Other,
+            (None, _) => return None
+        },
+        Event::Mouse(mouse) => match mouse.kind {
+            MouseEventKind::Moved | MouseEventKind::Drag(_) => InputEvent::PointerMoved { column: mouse.column },
+            MouseEventKind::ScrollUp => pressed(Action::RotateCw, 0),
+            MouseEventKind::ScrollDown => pressed(Action::RotateCcw, 0),
+            MouseEventKind::Down(MouseButton::Left) => pressed(Action::HardDrop, 0),
+            MouseEventKind::Down(MouseButton::Right) => pressed(Action::Hold, 0),
+            _ => return None
+        },
+        Event::Resize(_, _) => InputEvent::Resize,
+        Event::FocusLost => InputEvent::FocusLost,
+        _ => return None
+    };
+
+    return Some(event);
+}
+
+impl InputSource for TerminalInput {
+    fn poll(&mut self, now : u64, timeout : Duration) -> Option<InputEvent> {
+        self.input.poll(now, timeout)
+    }
+}
+
+//lets other code (bots, network peers) feed events through a channel; actions are stamped with the game time at which they were sent
+pub struct ChannelInput{
+    rx : Receiver<(Instant, InputEvent)>, //with the moment the event was sent
+}
+
+//the sending end of a ChannelInput
+#[derive(Clone)]
+pub struct InputSender{
+    tx : Sender<(Instant, InputEvent)>,
+}
+
+pub fn create_channel_input() -> (InputSender, ChannelInput){
+    let (tx, rx) = mpsc::channel::<(Instant, InputEvent)>();
+
+    return (InputSender{tx}, ChannelInput{rx});
+}
+
+//false once the source is gone
+pub fn send_input(sender : &InputSender, event : InputEvent) -> bool{
+    sender.tx.send((Instant::now(), event)).is_ok()
+}
+
+impl InputSource for ChannelInput {
+    fn poll(&mut self, now : u64, timeout : Duration) -> Option<InputEvent> {
+        match self.rx.recv_timeout(timeout) {
+            Ok((sent, InputEvent::Action(mut event))) => {
+                event.time = now.saturating_sub(sent.elapsed().as_millis() as u64); //the time the event waited in the channel
+                Some(InputEvent::Action(event))
+            },
+            Ok((_, event)) => Some(event),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => Some(InputEvent::Closed),
+        }
+    }
+}
+
+//plays back a fixed list of timed events; only the tests drive a game with it for now
+#[cfg(test)]
+pub mod scripted {
+    use std::collections::VecDeque;
+    use std::thread;
+    use std::time::Duration;
+
+    use super::InputSource;
+    use crate::input::{pressed, released, Action, InputEvent};
+
+    pub struct ScriptedInput{
+        events : VecDeque<InputEvent>, //sorted by time
+    }
+
+    //every action is pressed and released at the given game time
+    pub fn create_scripted_input(actions : &[(u64, Action)]) -> ScriptedInput{
+        let mut events : Vec<InputEvent> = vec![];
+
+        for (time, action) in actions.iter() {
+            events.push(pressed(*action, *time));
+            events.push(released(*action, *time));
+        }
+
+        return create_scripted_input_from_events(events);
+    }
+
+    pub fn create_scripted_input_from_events(mut events : Vec<InputEvent>) -> ScriptedInput{
+        events.sort_by_key(event_time); //stable, so simultaneous events keep their order
+
+        return ScriptedInput{events: events.into()};
+    }
+
+    fn event_time(event : &InputEvent) -> u64{
+        match event {
+            InputEvent::Action(action_event) => action_event.time,
+            _ => 0
+        }
+    }
+
+    impl InputSource for ScriptedInput {
+        fn poll(&mut self, now : u64, timeout : Duration) -> Option<InputEvent> {
+            let next_time = match self.events.front() {
+                Some(event) => event_time(event),
+                None => {
+                    thread::sleep(timeout);
+                    return None;
+                }
+            };
+
+            if next_time <= now {
+                return self.events.pop_front();
+            }
+
+            thread::sleep(timeout.min(Duration::from_millis(next_time - now)));
+            return None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::scripted::create_scripted_input;
+    use crate::board::field_grid;
+    use crate::cli::{ModeArgs, ModeName};
+    use crate::config::Config;
+    use crate::game::{hard_drop, update_game, Game, Outcome, State};
+    use crate::input::{ActionEvent, KeyBinding, KeyState};
+    use crossterm::event::{KeyCode, KeyEvent, KeyEventState, KeyModifiers, MouseEvent};
+    use crate::mode::create_mode;
+    use crate::play::{apply_input, new_game};
+
+    fn marathon() -> Game{
+        let mut config = Config::default();
+        config.rules.width = 10;
+        config.rules.height = 20;
+        config.rules.pieces = String::from("tetrominoes");
+
+        return new_game(&config, Some(3), create_mode(ModeName::Marathon, &ModeArgs::default()));
+    }
+
+    //the steering part of the loop of play_in_terminal, without the terminal; returns the time the source stopped the game
+    fn run_game(game : &mut Game, input : &mut dyn InputSource, end : u64) -> Option<u64>{
+        for now in 0..end {
+            update_game(game, now);
+
+            while let Some(event) = input.poll(now, Duration::ZERO) {
+                match event {
+                    InputEvent::Action(ActionEvent { action: Action::Quit, state: KeyState::Pressed, .. }) | InputEvent::Closed => return Some(now),
+                    InputEvent::Action(event) => { apply_input(game, event); },
+                    _ => {}
+                }
+            }
+
+            update_game(game, now);
+        }
+
+        return None;
+    }
+
+    //the game after a hard drop at the given time
+    fn dropped_at(game : &Game, time : u64) -> Game{
+        let mut game = game.clone();
+        update_game(&mut game, time);
+        hard_drop(&mut game);
+        update_game(&mut game, time);
+
+        return game;
+    }
+
+    #[test]
+    fn a_scripted_hard_drop_locks_the_brick_and_a_quit_stops_the_game() {
+        let mut game = marathon();
+        let expected = dropped_at(&game, 100);
+        let mut input = create_scripted_input(&[(100, Action::HardDrop), (200, Action::Quit), (300, Action::HardDrop)]);
+
+        assert_eq!(run_game(&mut game, &mut input, 1000), Some(200));
+        assert_eq!(game.field.bricks.len(), 1); //the drop after the quit never happened
+        assert_eq!(field_grid(&game.field), field_grid(&expected.field));
+        assert_eq!(game.field.bricks[0].locked_at, 100);
+        assert!(game.score > 0);
+        assert_eq!(game.score, expected.score);
+        assert!(game.state == State::Descend && game.outcome == Outcome::Playing && game.time == 200);
+    }
+
+    #[test]
+    fn channel_events_reach_the_game_until_the_sender_goes_away() {
+        let mut game = marathon();
+        let expected = dropped_at(&game, 0);
+        let (sender, mut input) = create_channel_input();
+        send_input(&sender, pressed(Action::HardDrop, 700)); //the time is the source's to set
+        send_input(&sender, released(Action::HardDrop, 700));
+        drop(sender);
+
+        assert_eq!(run_game(&mut game, &mut input, 1000), Some(0));
+        assert_eq!(game.field.bricks.len(), 1);
+        assert_eq!(field_grid(&game.field), field_grid(&expected.field));
+        assert_eq!(game.score, expected.score);
+        assert!(game.state == State::Descend && game.outcome == Outcome::Playing && game.time == 0);
+    }
+
+    #[test]
+    fn channel_actions_are_stamped_with_the_time_they_were_sent() {
+        let (sender, mut input) = create_channel_input();
+        send_input(&sender, pressed(Action::MoveLeft, 0));
+        send_input(&sender, InputEvent::Resize);
+        thread::sleep(Duration::from_millis(30));
+
+        let time = match input.poll(1000, Duration::ZERO) {
+            Some(InputEvent::Action(event)) => event.time,
+            _ => panic!("expected the press first")
+        };
+
+        assert!((500..=970).contains(&time), "{}", time); //it waited at least 30 ms in the channel
+        assert!(matches!(input.poll(1000, Duration::ZERO), Some(InputEvent::Resize)));
+        assert!(input.poll(1000, Duration::ZERO).is_none());
+    }
+
+    fn mouse(kind : MouseEventKind, column : u16) -> Event{
+        Event::Mouse(MouseEvent{kind, column, row: 5, modifiers: KeyModifiers::NONE})
+    }
+
+    fn key(code : KeyCode, kind : KeyEventKind) -> Event{
+        Event::Key(KeyEvent{code, modifiers: KeyModifiers::NONE, kind, state: KeyEventState::NONE})
+    }
+
+    fn is_press(event : Option<InputEvent>, expected : Action) -> bool{
+        matches!(event, Some(InputEvent::Action(ActionEvent { action, state: KeyState::Pressed, .. })) if action == expected)
+    }
+
+    #[test]
+    fn the_mouse_steers_the_brick_and_keys_map_to_their_bindings() {
+        let bindings = Bindings::from([(KeyBinding{code: KeyCode::Char(' '), modifiers: KeyModifiers::NONE}, Action::HardDrop)]);
+
+        assert!(matches!(terminal_event(&bindings, mouse(MouseEventKind::Moved, 7)), Some(InputEvent::PointerMoved { column: 7 })));
+        assert!(matches!(terminal_event(&bindings, mouse(MouseEventKind::Drag(MouseButton::Left), 12)), Some(InputEvent::PointerMoved { column: 12 })));
+        assert!(is_press(terminal_event(&bindings, mouse(MouseEventKind::ScrollUp, 7)), Action::RotateCw));
+        assert!(is_press(terminal_event(&bindings, mouse(MouseEventKind::ScrollDown, 7)), Action::RotateCcw));
+        assert!(is_press(terminal_event(&bindings, mouse(MouseEventKind::Down(MouseButton::Left), 7)), Action::HardDrop));
+        assert!(is_press(terminal_event(&bindings, mouse(MouseEventKind::Down(MouseButton::Right), 7)), Action::Hold));
+        assert!(terminal_event(&bindings, mouse(MouseEventKind::Up(MouseButton::Left), 7)).is_none());
+        assert!(terminal_event(&bindings, mouse(MouseEventKind::Down(MouseButton::Middle), 7)).is_none());
+
+        assert!(is_press(terminal_event(&bindings, key(KeyCode::Char(' '), KeyEventKind::Repeat)), Action::HardDrop));
+        assert!(matches!(terminal_event(&bindings, key(KeyCode::Char(' '), KeyEventKind::Release)), Some(InputEvent::Action(ActionEvent { action: Action::HardDrop, state: KeyState::Released, .. }))));
+        assert!(matches!(terminal_event(&bindings, key(KeyCode::Char('x'), KeyEventKind::Press)), Some(InputEvent::Other)));
+        assert!(terminal_event(&bindings, key(KeyCode::Char('x'), KeyEventKind::Release)).is_none());
+    }
+}
@@ -1,182 +1,211 @@
+#![allow(clippy::needless_return)] //the code base ends its functions with an explicit return
+
+mod animation;
+mod board;
+mod bot;
 mod brick;
+mod cli;
+mod config;
 mod field;
+mod fumen;
+mod game;
+mod input;
+mod input_source;
+mod menu;
+mod mode;
+mod pieces;
+mod play;
 mod utility;
 mod point;
-
-use std::io;
-use crate::brick::{generate_brick, print_brick, Brick, get_height, get_width, rotate, };
-use crate::field::{can_descend_brick, create_field, Field, will_have_collision, land_brick, print_field};
-use crate::point::{Point, translate_by};
-use crate::utility::{Keys, move_to_and_write, setup_console, shorten_interval, spawn_stdin_channel, get_screen_translated_vertices};
-
-use crate::State::{BrickGen, Descend, Stop, Touchdown};
+mod puzzle;
+mod race;
+mod replay;
+mod scores;
+mod screen;
+mod session;
+mod theme;
+
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use std::sync::Arc;
+use std::time::Duration;
+use clap::Parser;
+
+use crate::bot::spawn_bot;
+use crate::cli::{game_args, game_overrides, Cli, Command, ConfigCommand, FumenCommand, ModeArgs, ModeName, PlayArgs};
+use crate::config::{build_theme, config_path, load_config, parse_override, show_config, validate_config, write_default_config, Config, Override};
+use crate::fumen::{board_pages, decode_fumen, encode_fumen, render_pages};
+use crate::input::build_bindings;
+use crate::input_source::{create_channel_input, create_terminal_input, spawn_terminal_reader, InputSource};
+use crate::menu::{choose_mode, menu_hint, replay_hint};
+use crate::mode::{create_mode, GameMode};
+use crate::pieces::load_piece_set;
+use crate::play::{play_in_terminal, run_benchmark, watch_replay, PlayOptions};
+use crate::race::{find_best_replay, start_race, Race};
+use crate::replay::{load_replay, start_playback};
+use crate::scores::{load_scores, ranked_table, render_table, table_keys};
+use crate::screen::{create_screen, Screen};
+use crate::session::{load_saved_game, parse_seed, saved_mode_args, SavedGame};
 
 extern crate crossterm;
 
-use std::sync::mpsc::TryRecvError;
-use std::time::Instant;
-use num_traits::FromPrimitive;
-
-
-#[derive(Copy, Clone)]
-enum State { //state of the game
-    Touchdown,
-    BrickGen,
-    Descend,
-    Stop
+fn exit_with_error(err: String) -> ! {
+    eprintln!("{}", err);
+    process::exit(1);
 }
 
-fn rotate_brick(brick: &mut Brick, master_node_position : &Point, field: &Field) {
-    let future_width = get_height(&brick) as i32;
-    let fits_in_field = master_node_position.x + future_width < (field.width as i32);
-    let mut requested_brick = brick.clone();
-    rotate(&mut requested_brick);
+fn create_screen_from(config: &Config) -> Screen {
+    let theme = load_piece_set(&config.rules.pieces).and_then(|pieces| build_theme(config, &pieces)).unwrap_or_else(|err| exit_with_error(format!("Invalid configuration: {}", err)));
 
-    let mut future_mn_pos = master_node_position.clone();
-    translate_by(&mut future_mn_pos, &Point { x: 0, y: 1 });
-
-    let future_vertices = get_screen_translated_vertices(&requested_brick.vertices, &future_mn_pos);
+    create_screen(theme, config.visuals.style, config.visuals.ghost)
+}
 
-    let has_collision = will_have_collision(&future_vertices, &field);
+//the mode given on the command line, or the one picked from the menu; exits when the player quits the menu
+fn select_mode(args: &ModeArgs, screen: &Screen, input: &mut dyn InputSource, hint: &str) -> Arc<dyn GameMode> {
+    let name = match args.mode {
+        Some(name) => name,
+        None if args.puzzle.is_some() => ModeName::Puzzle,
+        None => choose_mode(screen, input, hint).unwrap_or_else(|| process::exit(0)),
+    };
 
-    if fits_in_field && (!has_collision) {
-        rotate(brick);
-        print_brick(&brick, &master_node_position);
-        print_field(&field);
-    }
+    create_mode(name, args)
 }
 
-fn move_brick_right(brick: &Brick, master_node_position : &mut Point, field: &Field) {
-    let mut future_mn_pos = master_node_position.clone();
-    translate_by(&mut future_mn_pos, &Point { x: 1, y: 0 });
-    let width = get_width(&brick) as i32;
-    let fits_in_field = future_mn_pos.x + width < (field.width as i32);
+//the saved game with its mode and seed, played with the rules it was saved with; exits when there is none
+fn load_resumed_game(config: &mut Config) -> (Arc<dyn GameMode>, u64, SavedGame) {
+    let saved = match load_saved_game() {
+        Ok(Some(saved)) => saved,
+        Ok(None) => exit_with_error(String::from("No saved game to resume, save one with session.save_on_quit = true")),
+        Err(err) => exit_with_error(format!("Cannot resume the saved game: {}", err)),
+    };
 
-    let future_vertices = get_screen_translated_vertices(&brick.vertices, &future_mn_pos);
+    let seed = parse_seed(&saved.seed).unwrap_or_else(|err| exit_with_error(format!("Cannot resume the saved game: {}", err)));
+    let args = saved_mode_args(&saved.mode).unwrap_or_else(|err| exit_with_error(format!("Cannot resume the saved game: {}", err)));
+    config.rules = saved.rules.clone();
+    validate_config(config).unwrap_or_else(|err| exit_with_error(format!("Cannot resume the saved game: {}", err)));
 
-    if fits_in_field && (!will_have_collision(&future_vertices, &field)){
-        translate_by(master_node_position, &Point { x: 1, y: 0 });
-        print_brick(&brick, &master_node_position);
-        print_field(&field);
-    }
+    return (create_mode(args.mode.unwrap_or(ModeName::Classic), &args), seed, saved);
 }
 
-fn move_brick_left(brick: &Brick, master_node_position : &mut Point, field: &Field) {
-    let mut future_mn_pos = master_node_position.clone();
-    translate_by(&mut future_mn_pos, &Point { x: -1, y: 0 });
-    let fits_in_field = future_mn_pos.x > 0;
-
-    let future_vertices = get_screen_translated_vertices(&brick.vertices, &future_mn_pos);
-
-    if fits_in_field && (!will_have_collision(&future_vertices, &field)){
-        translate_by(master_node_position, &Point { x: -1, y: 0 });
-        print_brick(&brick, &master_node_position);
-        print_field(&field);
+//the replay given to --ghost, or the best recorded sprint; exits when it cannot be raced
+fn load_race(ghost: &str, args: &ModeArgs, config: &Config) -> Race {
+    if args.mode.is_some_and(|mode| mode != ModeName::Sprint) {
+        exit_with_error(String::from("--ghost races a sprint, it cannot be played in another mode"));
     }
-}
 
-fn reset_master_node(brick: &Brick, master_node_position : &mut Point, field: &Field){
-    master_node_position.y = 0;
+    let path = match ghost {
+        "best" => find_best_replay().unwrap_or_else(|err| exit_with_error(err)),
+        file => PathBuf::from(file),
+    };
 
-    if master_node_position.x + brick.width_horizontal as i32 >= field.width as i32 {
-        master_node_position.x = field.width as i32 - brick.width_horizontal as i32 - 1;
-    }
+    return load_replay(&path).and_then(|replay| start_race(replay, config))
+        .unwrap_or_else(|err| exit_with_error(format!("Cannot race the replay: {}", err)));
 }
 
-
 fn main() {
-    let field_width = 7; //including frame
-    let field_height = 10; //including frame
-
-    let mut state : State = BrickGen;
-    let mut brick : Brick = generate_brick();
-    let mut field : Field = create_field(field_width, field_height);
-    let mut master_node_position = Point{x: ((field_width - 2) / 2) as i32, y: 0}; //master node is in the bottom left corner of a rectangle drawn over the brick
-
-    let start = Instant::now();
-    let mut interval = 1000; //how long will it take between each brick descent in ms
-    let mut score = 0; //how many intervals you kept the game going
-
-    let stdin_channel = spawn_stdin_channel();
-
-    setup_console();
+    let cli = Cli::parse();
+    let command = cli.command.unwrap_or(Command::Play(PlayArgs::default()));
 
-    loop {
-        // brick handling
-        match state {
-            Touchdown => {
-                land_brick(&brick, &master_node_position, &mut field);
-                print_field(&field);
+    let mut overrides = cli.settings.iter()
+        .map(|setting| parse_override(setting))
+        .collect::<Result<Vec<Override>, String>>()
+        .unwrap_or_else(|err| exit_with_error(err));
 
-                state = BrickGen;
-            },
-
-            BrickGen => {
-                brick = generate_brick();
-                reset_master_node(&brick, &mut master_node_position, &field);
-
-                if can_descend_brick(&brick, &master_node_position, &field) {
-                    state = Descend;
-                }
-                else {
-                    state = Stop;
-                }
-
-            },
-
-            Descend => {
-                if start.elapsed().as_millis() % interval == 1 {
-                    score += 1;
-
-                    if can_descend_brick(&brick, &master_node_position, &field) {
-                        translate_by(&mut master_node_position, &Point { x: 0, y: 1 });
-                        print_brick(&brick, &master_node_position);
-                        print_field(&field);
-                    }
-                    else {
-                        state = Touchdown;
-                    }
-
-                    interval = shorten_interval(interval);
-                }
-            },
+    if let Some(args) = game_args(&command) {
+        overrides.extend(game_overrides(args));
+    }
 
-            Stop => {
-                move_to_and_write(field.width as i32 / 2 - 5, field_height as i32 + 2, "Game over!");
-                move_to_and_write(field.width as i32 / 2 - 5, field_height as i32 + 3, format!("Your score: {}", score).as_str());
-                move_to_and_write(field.width as i32 / 2 - 5, field_height as i32 + 4, "Press any key to continue...");
-                let mut ignored = String::new();
-                io::stdin().read_line(&mut ignored).unwrap();
-                break;
+    //finding or creating the configuration file works even while its content is invalid
+    let load = || load_config(cli.config.as_ref(), &overrides).unwrap_or_else(|err| exit_with_error(format!("Invalid configuration: {}", err)));
+    let load_bindings = |config: &Config| build_bindings(&config.keys).unwrap_or_else(|err| exit_with_error(format!("Invalid configuration: {}", err)));
+
+    match command {
+        Command::Play(args) => {
+            let mut config = load();
+            let saved = if args.resume { Some(load_resumed_game(&mut config)) } else { None };
+            let bindings = load_bindings(&config);
+            let hint = menu_hint(&bindings);
+            let mut screen = create_screen_from(&config);
+            let mut input = create_terminal_input(bindings);
+
+            let race = args.ghost.as_ref().map(|ghost| load_race(ghost, &args.mode, &config));
+
+            let (mode, seed) = match &saved {
+                Some((mode, seed, _)) => (mode.clone(), Some(*seed)),
+                None if race.is_some() => (create_mode(ModeName::Sprint, &args.mode), args.game.seed),
+                None => (select_mode(&args.mode, &screen, &mut input, &hint), args.game.seed),
+            };
+
+            let options = PlayOptions{fumen: args.fumen, resume: saved.map(|(_, _, saved)| saved), record: args.record || config.session.record_replays, race};
+            play_in_terminal(&config, seed, mode, &mut screen, &mut input, None, options);
+        },
+
+        Command::Bot { delay, mode, game } => {
+            let config = load();
+            let bindings = load_bindings(&config);
+            let hint = menu_hint(&bindings);
+            let mut screen = create_screen_from(&config);
+            let (input_tx, mut input) = create_channel_input();
+            spawn_terminal_reader(bindings, input_tx.clone()); //the keyboard still pauses and quits
+            let mode = select_mode(&mode, &screen, &mut input, &hint);
+            let bot = spawn_bot(Duration::from_millis(delay), input_tx);
+            play_in_terminal(&config, game.seed, mode, &mut screen, &mut input, Some(bot), PlayOptions{record: config.session.record_replays, ..PlayOptions::default()});
+        },
+
+        Command::Bench { pieces, print_board, game } => run_benchmark(&load(), game.seed, pieces, print_board),
+
+        Command::Replay { file } => {
+            let config = load();
+            let bindings = load_bindings(&config);
+            let hint = replay_hint(&bindings);
+            let playback = load_replay(&file).and_then(|replay| start_playback(replay, &config))
+                .unwrap_or_else(|err| exit_with_error(format!("Cannot play the replay: {}", err)));
+            let mut screen = create_screen_from(&playback.config);
+            let mut input = create_terminal_input(bindings);
+            watch_replay(playback, &mut screen, &mut input, &hint);
+        },
+
+        Command::Scores { mode, rows } => {
+            let scores = load_scores().unwrap_or_else(|err| exit_with_error(format!("Cannot read the scores: {}", err)));
+            let keys : Vec<(ModeName, String)> = table_keys(&scores).into_iter().filter(|(name, _)| mode.is_none_or(|mode| mode == *name)).collect();
+
+            if keys.is_empty() {
+                println!("No scores recorded yet.");
             }
-        }
-
-        // steering handling
-        match stdin_channel.try_recv() {
-            Ok(key) => {
-                match FromPrimitive::from_u16(key) {
-                    Some(Keys::Up) => rotate_brick(&mut brick, &master_node_position, &field),
 
-                    Some(Keys::Right) => move_brick_right(&brick, &mut master_node_position, &field),
+            let tables : Vec<String> = keys.iter().map(|(name, settings)| render_table(*name, settings, &ranked_table(&scores, *name, settings), None, rows)).collect();
+            print!("{}", tables.join("\n"));
+        },
 
-                    Some(Keys::Left) => move_brick_left(&brick, &mut master_node_position, &field),
-
-                    Some(Keys::Abort) =>  {
-                        move_to_and_write(field.width as i32 / 2 - 5, field_height as i32 + 2, "Received abort");
-                        break;
-                    },
-
-                    _ => continue
+        Command::Fumen { action } => match action {
+            FumenCommand::Decode { fumen } => match decode_fumen(&fumen) {
+                Ok(pages) => print!("{}", render_pages(&pages)),
+                Err(err) => exit_with_error(format!("Invalid fumen: {}", err)),
+            },
+            FumenCommand::Encode { files } => {
+                let texts = files.iter()
+                    .map(|file| fs::read_to_string(file).map_err(|err| format!("{}: {}", file.display(), err)))
+                    .collect::<Result<Vec<String>, String>>()
+                    .unwrap_or_else(|err| exit_with_error(err));
+
+                match board_pages(&texts) {
+                    Ok(pages) => println!("{}", encode_fumen(&pages)),
+                    Err(err) => exit_with_error(err),
                 }
             },
+        },
 
-            Err(TryRecvError::Empty) => {
-                continue;
+        Command::Config { action } => match action.unwrap_or(ConfigCommand::Show) {
+            ConfigCommand::Path => match cli.config.clone().or_else(config_path) {
+                Some(path) => println!("{}", path.display()),
+                None => exit_with_error(String::from("Cannot determine the configuration directory")),
             },
-
-            Err(TryRecvError::Disconnected) => {
-                panic!("Channel disconnected");
+            ConfigCommand::Show => print!("{}", show_config(&load())),
+            ConfigCommand::Init => match write_default_config(cli.config.as_ref()) {
+                Ok(path) => println!("Wrote {}", path.display()),
+                Err(err) => exit_with_error(err),
             },
-        }
+        },
     }
 }
@@ -0,0 +1,68 @@
+use std::time::Duration;
+
+use crate::cli::{ModeArgs, ModeName};
+use crate::input::{first_key, Action, ActionEvent, Bindings, InputEvent, KeyState};
+use crate::input_source::InputSource;
+use crate::mode::{create_mode, MODE_NAMES};
+use crate::screen::{write_at, Screen};
+use crate::utility::{clear_console, restore_console, setup_console};
+
+//"Up/Down: choose  Space: start  q: quit" with the player's own keys
+pub fn menu_hint(bindings : &Bindings) -> String{
+    let key = |action : Action| first_key(bindings, action).unwrap_or_else(|| String::from("?"));
+
+    format!("{}/{}: choose  {}: start  {}: quit", key(Action::RotateCw), key(Action::SoftDrop), key(Action::HardDrop), key(Action::Quit))
+}
+
+//"p: pause  Left/Right: seek  Up/Down: speed" and "r: start over  q: quit" with the player's own keys, for watching a replay
+pub fn replay_hint(bindings : &Bindings) -> Vec<String>{
+    let key = |action : Action| first_key(bindings, action).unwrap_or_else(|| String::from("?"));
+
+    vec![
+        format!("{}: pause  {}/{}: seek  {}/{}: speed", key(Action::Pause), key(Action::MoveLeft), key(Action::MoveRight), key(Action::RotateCw), key(Action::SoftDrop)),
+        format!("{}: start over  {}: quit", key(Action::Restart), key(Action::Quit)),
+    ]
+}
+
+fn print_menu(screen : &Screen, selected : usize, hint : &str){
+    clear_console();
+    write_at(screen, 0, 0, "Choose a game mode", screen.theme.hud);
+
+    for (idx, name) in MODE_NAMES.iter().enumerate() {
+        let marker = if idx == selected { ">" } else { " " };
+        write_at(screen, 0, idx as i32 + 2, format!("{} {}", marker, create_mode(*name, &ModeArgs::default()).name()).as_str(), screen.theme.hud);
+    }
+
+    let below = MODE_NAMES.len() as i32 + 3;
+    write_at(screen, 0, below, create_mode(MODE_NAMES[selected], &ModeArgs::default()).goal().as_str(), screen.theme.ghost);
+    write_at(screen, 0, below + 2, hint, screen.theme.hud);
+}
+
+//lets the player pick a mode with the rotate / soft drop (or move) keys; None when they quit
+pub fn choose_mode(screen : &Screen, input : &mut dyn InputSource, hint : &str) -> Option<ModeName>{
+    let mut selected = 0;
+
+    setup_console();
+    print_menu(screen, selected, hint);
+
+    let choice = loop {
+        match input.poll(0, Duration::from_millis(100)) {
+            Some(InputEvent::Action(ActionEvent { action, state: KeyState::Pressed, .. })) => match action {
+                Action::RotateCw | Action::MoveLeft => selected = (selected + MODE_NAMES.len() - 1) % MODE_NAMES.len(),
+                Action::SoftDrop | Action::MoveRight => selected = (selected + 1) % MODE_NAMES.len(),
+                Action::HardDrop => break Some(MODE_NAMES[selected]),
+                Action::Quit => break None,
+                _ => continue
+            },
+            Some(InputEvent::Closed) => break None,
+            Some(InputEvent::Resize) => {},
+            _ => continue
+        }
+
+        print_menu(screen, selected, hint);
+    };
+
+    restore_console();
+
+    return choice;
+}
@@ -0,0 +1,486 @@
+use std::sync::Arc;
+use clap::ValueEnum;
+
+use crate::cli::{ModeArgs, ModeName};
+use crate::field::{count_garbage_rows, drop_bottom_rows, find_full_rows, is_perfect_clear, place_cells};
+use crate::field::ClearGravity;
+use crate::game::{add_garbage, level_interval, Game, GravityMode, Outcome, RotationSystem};
+use crate::pieces::{load_piece_set, without_mirror_images};
+use crate::point::Point;
+use crate::puzzle::{describe_goal, load_puzzle, Goal, Puzzle, PUZZLE_NAMES};
+
+pub const MODE_NAMES : [ModeName; 8] = [ModeName::Classic, ModeName::Marathon, ModeName::Sprint, ModeName::Ultra, ModeName::Zen, ModeName::Dig, ModeName::Pentomino, ModeName::Puzzle];
+
+const MARATHON_LINES : u32 = 150;
+const SPRINT_LINES : u32 = 40;
+const ULTRA_MS : u64 = 2 * 60 * 1000;
+const ZEN_LEVEL : u32 = 1; //gravity of zen mode, for good
+const DIG_ROWS : u16 = 10;
+const DIG_HEADROOM : u16 = 4; //rows kept free of garbage for the bricks to spawn
+const PENTOMINO_FIELD : (u16, u16) = (10, 20); //the smallest field pentomino mode is played on
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Scoring {
+    Survival, //a point for every interval the game keeps going
+    Guideline, //100 / 300 / 500 / 800 points per clear times the level, 1 per soft dropped and 2 per hard dropped row
+}
+
+//how the high score table of a mode orders its games
+#[derive(Copy, Clone, PartialEq)]
+pub enum Ranking {
+    Score, //highest first
+    Time, //fastest first, won games only
+}
+
+//the rules on top of the engine: when a game is won or lost, how it is scored and what the HUD shows
+pub trait GameMode : Send + Sync {
+    fn name(&self) -> &'static str;
+    fn goal(&self) -> String;
+    fn scoring(&self) -> Scoring;
+
+    fn ranking(&self) -> Ranking {
+        Ranking::Score
+    }
+
+    //the command line arguments which create the mode again, e.g. for a saved game
+    fn args(&self) -> ModeArgs {
+        ModeArgs{mode: ModeName::from_str(self.name(), true).ok(), ..ModeArgs::default()}
+    }
+
+    //the field the mode is played on, given the one of the rules
+    fn field_size(&self, width : u16, height : u16) -> (u16, u16) {
+        (width, height)
+    }
+
+    //adjusts a new game to the mode, e.g. its gravity
+    fn setup(&self, _game : &mut Game) {}
+
+    //checked whenever a brick locks, once the rows it cleared are counted
+    fn is_won(&self, _game : &Game) -> bool {
+        false
+    }
+
+    //called when a new brick has no room; returns whether the game goes on, after making room for the brick
+    fn top_out(&self, _game : &mut Game) -> bool {
+        false
+    }
+
+    //whether the board is saved when the player quits and restored when the mode is played again
+    fn keeps_session(&self) -> bool {
+        false
+    }
+
+    //the game ends once this much game time has passed
+    fn time_limit(&self) -> Option<u64> {
+        None
+    }
+
+    //whether the HUD shows a running clock, which needs regular redraws
+    fn is_timed(&self) -> bool {
+        self.time_limit().is_some()
+    }
+
+    fn hud(&self, game : &Game) -> Vec<String>;
+
+    //the lines shown when the game is over
+    fn summary(&self, game : &Game) -> Vec<String>;
+}
+
+struct Classic;
+
+struct Marathon{
+    lines : Option<u32>, //endless without a goal
+}
+
+struct Sprint;
+
+struct Ultra;
+
+struct Zen;
+
+struct Dig{
+    rows : u16,
+}
+
+struct Pentomino{
+    free : bool, //the 12 free pentominoes instead of the 18 one-sided ones
+}
+
+struct PuzzleMode{
+    puzzle : Puzzle,
+}
+
+pub fn create_mode(name : ModeName, args : &ModeArgs) -> Arc<dyn GameMode>{
+    match name {
+        ModeName::Classic => Arc::new(Classic),
+        ModeName::Marathon => Arc::new(Marathon{lines: if args.endless { None } else { Some(MARATHON_LINES) }}),
+        ModeName::Dig => Arc::new(Dig{rows: args.garbage.unwrap_or(DIG_ROWS)}),
+        ModeName::Pentomino => Arc::new(Pentomino{free: args.free}),
+        ModeName::Puzzle => Arc::new(PuzzleMode{puzzle: args.puzzle.clone().unwrap_or_else(|| load_puzzle(PUZZLE_NAMES[0]).unwrap())}),
+        ModeName::Sprint => Arc::new(Sprint),
+        ModeName::Ultra => Arc::new(Ultra),
+        ModeName::Zen => Arc::new(Zen),
+    }
+}
+
+//game time of the end, or the current time while the game goes on
+pub fn play_time(game : &Game) -> u64{
+    game.ended_at.unwrap_or(game.time)
+}
+
+//garbage rows as they were added, a big one counting once
+fn garbage_left(game : &Game) -> usize{
+    count_garbage_rows(&game.field).div_ceil(game.scale as usize)
+}
+
+//"1:02.345"
+pub fn format_time(ms : u64) -> String{
+    format!("{}:{:02}.{:03}", ms / 60000, ms / 1000 % 60, ms % 1000)
+}
+
+impl GameMode for Classic {
+    fn name(&self) -> &'static str {
+        "Classic"
+    }
+
+    fn goal(&self) -> String {
+        String::from("Survive as long as you can")
+    }
+
+    fn scoring(&self) -> Scoring {
+        Scoring::Survival
+    }
+
+    fn hud(&self, game : &Game) -> Vec<String> {
+        vec![format!("Score: {}", game.score)]
+    }
+
+    fn summary(&self, game : &Game) -> Vec<String> {
+        vec![String::from("Game over!"), format!("Your score: {}", game.score)]
+    }
+}
+
+impl GameMode for Marathon {
+    fn name(&self) -> &'static str {
+        "Marathon"
+    }
+
+    fn goal(&self) -> String {
+        match self.lines {
+            Some(lines) => format!("Clear {} lines, speeding up every 10", lines),
+            None => String::from("Clear lines until you top out"),
+        }
+    }
+
+    fn scoring(&self) -> Scoring {
+        Scoring::Guideline
+    }
+
+    fn args(&self) -> ModeArgs {
+        ModeArgs{mode: Some(ModeName::Marathon), endless: self.lines.is_none(), ..ModeArgs::default()}
+    }
+
+    fn setup(&self, game : &mut Game) {
+        game.gravity = GravityMode::Level;
+    }
+
+    fn is_won(&self, game : &Game) -> bool {
+        self.lines.is_some_and(|lines| game.lines >= lines)
+    }
+
+    fn hud(&self, game : &Game) -> Vec<String> {
+        let lines = match self.lines {
+            Some(goal) => format!("Lines: {}/{}", game.lines, goal),
+            None => format!("Lines: {}", game.lines),
+        };
+
+        vec![format!("Score: {}", game.score), format!("Level: {}", game.level), lines]
+    }
+
+    fn summary(&self, game : &Game) -> Vec<String> {
+        let headline = if game.outcome == Outcome::Won { "Marathon complete!" } else { "Game over!" };
+
+        vec![String::from(headline), format!("Your score: {}", game.score)]
+    }
+}
+
+impl GameMode for Sprint {
+    fn name(&self) -> &'static str {
+        "Sprint"
+    }
+
+    fn goal(&self) -> String {
+        format!("Clear {} lines as fast as you can", SPRINT_LINES)
+    }
+
+    fn scoring(&self) -> Scoring {
+        Scoring::Guideline
+    }
+
+    fn ranking(&self) -> Ranking {
+        Ranking::Time
+    }
+
+    fn setup(&self, game : &mut Game) {
+        game.gravity = GravityMode::Fixed;
+    }
+
+    fn is_won(&self, game : &Game) -> bool {
+        game.lines >= SPRINT_LINES
+    }
+
+    fn is_timed(&self) -> bool {
+        true
+    }
+
+    fn hud(&self, game : &Game) -> Vec<String> {
+        vec![format!("Time: {}", format_time(play_time(game))), format!("Lines: {}/{}", game.lines, SPRINT_LINES)]
+    }
+
+    fn summary(&self, game : &Game) -> Vec<String> {
+        match game.outcome {
+            Outcome::Won => vec![String::from("Sprint complete!"), format!("Your time: {}", format_time(play_time(game)))],
+            _ => vec![String::from("Game over!"), format!("Lines: {}/{}", game.lines, SPRINT_LINES)],
+        }
+    }
+}
+
+impl GameMode for Ultra {
+    fn name(&self) -> &'static str {
+        "Ultra"
+    }
+
+    fn goal(&self) -> String {
+        format!("Score as much as you can in {} minutes", ULTRA_MS / 60000)
+    }
+
+    fn scoring(&self) -> Scoring {
+        Scoring::Guideline
+    }
+
+    fn setup(&self, game : &mut Game) {
+        game.gravity = GravityMode::Fixed;
+    }
+
+    fn time_limit(&self) -> Option<u64> {
+        Some(ULTRA_MS)
+    }
+
+    fn hud(&self, game : &Game) -> Vec<String> {
+        vec![
+            format!("Score: {}", game.score),
+            format!("Time left: {}", format_time(ULTRA_MS.saturating_sub(play_time(game)))),
+            format!("Lines: {}", game.lines),
+        ]
+    }
+
+    fn summary(&self, game : &Game) -> Vec<String> {
+        let headline = if game.outcome == Outcome::Won { "Time's up!" } else { "Game over!" };
+
+        vec![String::from(headline), format!("Your score: {}", game.score)]
+    }
+}
+
+impl GameMode for Zen {
+    fn name(&self) -> &'static str {
+        "Zen"
+    }
+
+    fn goal(&self) -> String {
+        String::from("Relax: no speed up, no top out, quit to save")
+    }
+
+    fn scoring(&self) -> Scoring {
+        Scoring::Guideline
+    }
+
+    fn setup(&self, game : &mut Game) {
+        game.gravity = GravityMode::Fixed;
+        game.level = ZEN_LEVEL;
+        game.interval = level_interval(ZEN_LEVEL);
+    }
+
+    //the bottom half of the board goes away instead
+    fn top_out(&self, game : &mut Game) -> bool {
+        let rows = game.field.height.div_ceil(2).next_multiple_of(game.scale); //whole rows of big bricks
+        drop_bottom_rows(&mut game.field, rows);
+
+        true
+    }
+
+    fn keeps_session(&self) -> bool {
+        true
+    }
+
+    fn hud(&self, game : &Game) -> Vec<String> {
+        vec![format!("Score: {}", game.score), format!("Lines: {}", game.lines)]
+    }
+
+    fn summary(&self, game : &Game) -> Vec<String> {
+        vec![String::from("Game over!"), format!("Your score: {}", game.score)]
+    }
+}
+
+impl GameMode for Dig {
+    fn name(&self) -> &'static str {
+        "Dig"
+    }
+
+    fn goal(&self) -> String {
+        format!("Dig through {} rows of garbage as fast as you can", self.rows)
+    }
+
+    fn scoring(&self) -> Scoring {
+        Scoring::Guideline
+    }
+
+    fn ranking(&self) -> Ranking {
+        Ranking::Time
+    }
+
+    fn args(&self) -> ModeArgs {
+        ModeArgs{mode: Some(ModeName::Dig), garbage: Some(self.rows), ..ModeArgs::default()}
+    }
+
+    fn setup(&self, game : &mut Game) {
+        game.gravity = GravityMode::Fixed;
+        add_garbage(game, self.rows.min((game.field.height / game.scale).saturating_sub(DIG_HEADROOM)));
+    }
+
+    fn is_won(&self, game : &Game) -> bool {
+        garbage_left(game) == 0
+    }
+
+    fn is_timed(&self) -> bool {
+        true
+    }
+
+    fn hud(&self, game : &Game) -> Vec<String> {
+        vec![format!("Time: {}", format_time(play_time(game))), format!("Garbage: {}", garbage_left(game))]
+    }
+
+    fn summary(&self, game : &Game) -> Vec<String> {
+        match game.outcome {
+            Outcome::Won => vec![String::from("Dig complete!"), format!("Your time: {}", format_time(play_time(game)))],
+            _ => vec![String::from("Game over!"), format!("Garbage left: {}", garbage_left(game))],
+        }
+    }
+}
+
+impl GameMode for Pentomino {
+    fn name(&self) -> &'static str {
+        "Pentomino"
+    }
+
+    fn goal(&self) -> String {
+        let pieces = if self.free { "12 free" } else { "18 one-sided" };
+
+        format!("Clear lines with the {} pentominoes, speeding up every 10", pieces)
+    }
+
+    fn scoring(&self) -> Scoring {
+        Scoring::Guideline
+    }
+
+    fn args(&self) -> ModeArgs {
+        ModeArgs{mode: Some(ModeName::Pentomino), free: self.free, ..ModeArgs::default()}
+    }
+
+    fn field_size(&self, width : u16, height : u16) -> (u16, u16) {
+        (width.max(PENTOMINO_FIELD.0), height.max(PENTOMINO_FIELD.1))
+    }
+
+    fn setup(&self, game : &mut Game) {
+        let pieces = load_piece_set("pentominoes").unwrap();
+
+        game.pieces = Arc::new(if self.free { without_mirror_images(&pieces) } else { pieces });
+        game.bag.clear();
+        game.gravity = GravityMode::Level;
+        game.rotation = RotationSystem::Kicks; //five cells long pieces rarely turn in place
+    }
+
+    fn hud(&self, game : &Game) -> Vec<String> {
+        vec![format!("Score: {}", game.score), format!("Level: {}", game.level), format!("Lines: {}", game.lines)]
+    }
+
+    fn summary(&self, game : &Game) -> Vec<String> {
+        vec![String::from("Game over!"), format!("Your score: {}", game.score)]
+    }
+}
+
+impl GameMode for PuzzleMode {
+    fn name(&self) -> &'static str {
+        "Puzzle"
+    }
+
+    fn goal(&self) -> String {
+        format!("{}: {}", self.puzzle.name, describe_goal(self.puzzle.goal))
+    }
+
+    fn scoring(&self) -> Scoring {
+        Scoring::Guideline
+    }
+
+    fn ranking(&self) -> Ranking {
+        Ranking::Time
+    }
+
+    fn args(&self) -> ModeArgs {
+        ModeArgs{mode: Some(ModeName::Puzzle), puzzle: Some(self.puzzle.clone()), ..ModeArgs::default()}
+    }
+
+    //puzzles bring their own field
+    fn field_size(&self, _width : u16, _height : u16) -> (u16, u16) {
+        (self.puzzle.width, self.puzzle.height)
+    }
+
+    fn setup(&self, game : &mut Game) {
+        let scale = game.scale as i32;
+        let bottom = game.field.height as i32 - 1;
+        let mut cells : Vec<(Point, char)> = vec![];
+
+        for (cell, kind) in self.puzzle.cells.iter() {
+            for dx in 0..scale {
+                for dy in 0..scale {
+                    cells.push((Point{x: 1 + cell.x * scale + dx, y: bottom - cell.y * scale - dy}, *kind));
+                }
+            }
+        }
+
+        place_cells(&mut game.field, &cells, game.time);
+        game.pieces = self.puzzle.pieces.clone();
+        game.sequence = Some(self.puzzle.sequence.iter().rev().copied().collect());
+        game.gravity = GravityMode::Fixed;
+        game.clear_gravity = ClearGravity::Naive; //the board is made of loose cells, which would fall apart otherwise
+        game.rotation = RotationSystem::Kicks;
+    }
+
+    fn is_won(&self, game : &Game) -> bool {
+        match self.puzzle.goal {
+            Goal::Lines(lines) => game.lines >= lines,
+            Goal::PerfectClear => is_perfect_clear(&game.field),
+            Goal::TSpinTriple => game.t_spin && find_full_rows(&game.field).len() == 3 * game.scale as usize,
+            Goal::Survive(pieces) => game.placed >= pieces,
+        }
+    }
+
+    fn hud(&self, game : &Game) -> Vec<String> {
+        let progress = match self.puzzle.goal {
+            Goal::Lines(lines) => format!("Lines: {}/{}", game.lines, lines),
+            Goal::PerfectClear => String::from("Goal: perfect clear"),
+            Goal::TSpinTriple => String::from("Goal: T-spin triple"),
+            Goal::Survive(pieces) => format!("Pieces: {}/{}", game.placed, pieces),
+        };
+        let next : String = game.sequence.iter().flatten().rev().map(|idx| game.pieces.bricks[*idx].kind).collect();
+
+        let next = if next.is_empty() { String::from("-") } else { next };
+
+        vec![self.puzzle.name.clone(), progress, format!("Next: {}", next)]
+    }
+
+    fn summary(&self, game : &Game) -> Vec<String> {
+        let headline = if game.outcome == Outcome::Won { "Puzzle solved!" } else { "Puzzle failed!" };
+
+        vec![String::from(headline), format!("Goal: {}", describe_goal(self.puzzle.goal))]
+    }
+}
@@ -0,0 +1,254 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use crossterm::style::Color;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::brick::{build_brick, rotate, Brick};
+use crate::config::config_dir;
+use crate::point::Point;
+use crate::theme::parse_color;
+
+pub const PIECE_SET_NAMES : [&str; 4] = ["legacy", "tetrominoes", "trominoes", "pentominoes"];
+
+//a piece set file, see pieces/*.toml
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PieceSetFile{
+    name : String,
+    kicks : Option<Vec<[i32; 2]>>, //for the pieces without a table of their own
+    pieces : Vec<PieceFile>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PieceFile{
+    name : String, //a single letter, the theme colors the piece by it
+    cells : Vec<[i32; 2]>, //x to the right, y up
+    color : Option<String>,
+    centre : Option<[f64; 2]>, //a cell or a corner between cells; derived from the bounding box when missing
+    #[serde(default)]
+    spawn_offset : [i32; 2], //columns to the right and rows up from where a piece spawns
+    kicks : Option<Vec<[i32; 2]>>, //offsets (y up) tried in order when a rotation in place is blocked
+}
+
+#[derive(Clone)]
+pub struct PieceSet{
+    pub(crate) name : String,
+    pub(crate) bricks : Vec<Brick>, //in the order of the file, as spawned
+    pub(crate) colors : HashMap<char, Color>, //piece letter -> color, applied on top of the theme
+}
+
+fn builtin_piece_set(name : &str) -> Option<&'static str>{
+    match name {
+        "legacy" => Some(include_str!("../pieces/legacy.toml")),
+        "tetrominoes" => Some(include_str!("../pieces/tetrominoes.toml")),
+        "trominoes" => Some(include_str!("../pieces/trominoes.toml")),
+        "pentominoes" => Some(include_str!("../pieces/pentominoes.toml")),
+        _ => None
+    }
+}
+
+//kick offsets are written with y up, the field counts rows down
+fn parse_kicks(kicks : &[[i32; 2]]) -> Vec<Point>{
+    kicks.iter().map(|[x, y]| Point{x: *x, y: -*y}).collect()
+}
+
+fn parse_piece(piece : &PieceFile, set_kicks : &Option<Vec<[i32; 2]>>) -> Result<Brick, String>{
+    let mut letters = piece.name.chars();
+    let kind = match (letters.next(), letters.next()) {
+        (Some(kind), None) if !kind.is_whitespace() => kind,
+        _ => return Err(format!("piece '{}': the name must be a single letter", piece.name))
+    };
+
+    if piece.cells.is_empty() {
+        return Err(format!("piece '{}': no cells", piece.name));
+    }
+
+    //the vertices start at the bottom left corner of the bounding box
+    let min_x = piece.cells.iter().map(|[x, _]| *x).min().unwrap();
+    let min_y = piece.cells.iter().map(|[_, y]| *y).min().unwrap();
+    let vertices : Vec<Point> = piece.cells.iter().map(|[x, y]| Point{x: x - min_x, y: y - min_y}).collect();
+
+    if vertices.iter().enumerate().any(|(idx, cell)| vertices[..idx].contains(cell)) {
+        return Err(format!("piece '{}': a cell is listed twice", piece.name));
+    }
+
+    let mut brick = build_brick(vertices, kind);
+
+    if let Some([x, y]) = piece.centre {
+        let (double_x, double_y) = ((x - min_x as f64) * 2.0, (y - min_y as f64) * 2.0);
+
+        //on a cell both doubled coordinates are even, on a corner both are odd; anything else would not turn onto the grid
+        if double_x.fract() != 0.0 || double_y.fract() != 0.0 || (double_x as i32 - double_y as i32) % 2 != 0 {
+            return Err(format!("piece '{}': the centre must be a cell or a corner between cells, got [{}, {}]", piece.name, x, y));
+        }
+
+        brick.centre = Point{x: double_x as i32, y: double_y as i32};
+    }
+
+    brick.spawn_offset = Point{x: piece.spawn_offset[0], y: piece.spawn_offset[1]};
+    brick.kicks = piece.kicks.as_ref().or(set_kicks.as_ref()).map(|kicks| parse_kicks(kicks));
+
+    return Ok(brick);
+}
+
+pub fn parse_piece_set(text : &str) -> Result<PieceSet, String>{
+    let file : PieceSetFile = toml::from_str(text).map_err(|err| err.to_string().trim_end().replace('\n', " "))?;
+
+    if file.pieces.is_empty() {
+        return Err(String::from("the set has no pieces"));
+    }
+
+    let mut bricks : Vec<Brick> = vec![];
+    let mut colors : HashMap<char, Color> = HashMap::new();
+
+    for piece in file.pieces.iter() {
+        let brick = parse_piece(piece, &file.kicks)?;
+
+        if bricks.iter().any(|other| other.kind == brick.kind) {
+            return Err(format!("piece '{}': the name is used twice", piece.name));
+        }
+
+        if let Some(color) = &piece.color {
+            colors.insert(brick.kind, parse_color(color).map_err(|err| format!("piece '{}': {}", piece.name, err))?);
+        }
+
+        bricks.push(brick);
+    }
+
+    return Ok(PieceSet{name: file.name, bricks, colors});
+}
+
+//~/.config/tetris/pieces/<name>.toml
+fn piece_set_path(name : &str) -> Option<PathBuf>{
+    Some(config_dir()?.join("pieces").join(format!("{}.toml", name)))
+}
+
+//a built-in set, a set in the pieces directory of the configuration or a path to a set file
+pub fn load_piece_set(name : &str) -> Result<PieceSet, String>{
+    if let Some(text) = builtin_piece_set(name) {
+        return Ok(parse_piece_set(text).expect("the built-in piece sets are valid"));
+    }
+
+    let path = match piece_set_path(name) {
+        Some(path) if path.exists() => path,
+        _ => PathBuf::from(name)
+    };
+
+    if !path.exists() {
+        return Err(format!("rules.pieces: unknown piece set '{}', expected one of: {}, or a piece set file", name, PIECE_SET_NAMES.join(", ")));
+    }
+
+    let text = fs::read_to_string(&path).map_err(|err| format!("{}: {}", path.display(), err))?;
+
+    return parse_piece_set(&text).map_err(|err| format!("{}: {}", path.display(), err));
+}
+
+//the cells moved to the origin and sorted, so that equal shapes compare equal
+fn normalized(cells : &[Point]) -> Vec<(i32, i32)>{
+    let min_x = cells.iter().map(|cell| cell.x).min().unwrap_or(0);
+    let min_y = cells.iter().map(|cell| cell.y).min().unwrap_or(0);
+    let mut normalized : Vec<(i32, i32)> = cells.iter().map(|cell| (cell.x - min_x, cell.y - min_y)).collect();
+    normalized.sort();
+
+    return normalized;
+}
+
+//the four states the brick turns through, and those of its mirror image as well when mirrored is set
+fn orientations(brick : &Brick, mirrored : bool) -> Vec<Vec<(i32, i32)>>{
+    let mut turned = brick.clone();
+    let mut states : Vec<Vec<(i32, i32)>> = vec![];
+
+    for _ in 0..4 {
+        states.push(normalized(&turned.vertices));
+        rotate(&mut turned);
+    }
+
+    if mirrored {
+        let mirror : Vec<Point> = brick.vertices.iter().map(|cell| Point{x: -cell.x, y: cell.y}).collect();
+        states.extend(orientations(&build_brick(normalized(&mirror).iter().map(|(x, y)| Point{x: *x, y: *y}).collect(), brick.kind), false));
+    }
+
+    return states;
+}
+
+//leaves out every piece which is the mirror image of an earlier one, e.g. the 18 one-sided pentominoes become the 12 free ones
+pub fn without_mirror_images(set : &PieceSet) -> PieceSet{
+    let mut free = PieceSet{name: format!("free {}", set.name), bricks: vec![], colors: set.colors.clone()};
+
+    for brick in set.bricks.iter() {
+        let states = orientations(brick, true);
+
+        if !free.bricks.iter().any(|kept| states.contains(&normalized(&kept.vertices))) {
+            free.bricks.push(brick.clone());
+        }
+    }
+
+    return free;
+}
+
+pub fn draw_piece<R: Rng + ?Sized>(set : &PieceSet, rng : &mut R) -> Brick{
+    set.bricks[rng.random_range(0..set.bricks.len() as u32) as usize].clone() //drawn as u32, which keeps the sequences of older versions
+}
+
+//every piece of the set once, in random order
+pub fn generate_bag<R: Rng + ?Sized>(set : &PieceSet, rng : &mut R) -> Vec<usize>{
+    let mut bag : Vec<usize> = (0..set.bricks.len()).collect();
+    bag.shuffle(rng);
+
+    return bag;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::brick::rotate;
+
+    fn sorted(cells : &[Point]) -> Vec<(i32, i32)> {
+        let mut cells : Vec<(i32, i32)> = cells.iter().map(|cell| (cell.x, cell.y)).collect();
+        cells.sort();
+
+        return cells;
+    }
+
+    #[test]
+    fn every_built_in_piece_comes_back_in_place_after_four_quarter_turns() {
+        for name in PIECE_SET_NAMES {
+            let set = load_piece_set(name).unwrap();
+
+            for brick in set.bricks.iter() {
+                let mut turned = brick.clone();
+
+                let mut moved = Point{x: 0, y: 0};
+
+                for _ in 0..4 {
+                    let shift = rotate(&mut turned);
+                    moved = Point{x: moved.x + shift.x, y: moved.y + shift.y};
+                }
+
+                assert_eq!(sorted(&turned.vertices), sorted(&brick.vertices), "piece {} of {}", brick.kind, name);
+                assert!(moved == Point{x: 0, y: 0}, "piece {} of {} wandered off while turning", brick.kind, name);
+            }
+        }
+    }
+
+    #[test]
+    fn the_one_sided_pentominoes_make_twelve_free_ones() {
+        let one_sided = load_piece_set("pentominoes").unwrap();
+        let free = without_mirror_images(&one_sided);
+
+        assert_eq!(one_sided.bricks.len(), 18);
+        assert_eq!(free.bricks.len(), 12);
+        assert!(free.bricks.iter().all(|brick| brick.kind.is_ascii_uppercase()));
+    }
+
+    #[test]
+    fn a_centre_between_a_cell_and_a_corner_is_rejected() {
+        let text = "name = \"odd\"\n[[pieces]]\nname = \"A\"\ncells = [[0, 0], [1, 0]]\ncentre = [0.5, 0]\n";
+
+        assert!(parse_piece_set(text).is_err_and(|err| err.contains("centre")));
+    }
+}
@@ -0,0 +1,607 @@
+use std::sync::Arc;
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
+
+use crate::animation::{AnimationSettings, create_animations, is_animating, is_topping_out, line_clear_delay, print_animations, start_animation, Animations};
+use crate::bot::plan_placement;
+use crate::brick::{print_brick, print_ghost};
+use crate::board::{game_board, render_board};
+use crate::config::{build_theme, Config, Rules};
+use crate::fumen::{encode_fumen, game_pages};
+use crate::field::{find_drop_position, print_field, BoardVisibility};
+use crate::game::{apply_action, create_game, is_brick_visible, level_interval, move_brick_to_column, press_action, release_action, update_game, Game, GameEvent, Outcome, State};
+use crate::input::{Action, ActionEvent, InputEvent, KeyState};
+use crate::input_source::InputSource;
+use crate::cli::{ModeArgs, ModeName};
+use crate::mode::{create_mode, format_time, GameMode};
+use crate::pieces::load_piece_set;
+use crate::race::{race_hud, update_race, Race};
+use crate::replay::{recorded_replay, save_replay, seek, Playback};
+use crate::scores::{game_score, player_name, record_score, render_table, TABLE_ROWS};
+use crate::session::{load_session, remove_saved_game, resume_game, restore_session, save_game, save_session, saved_game, SavedGame};
+use crate::screen::{fit_screen_to_terminal, place_beside, print_too_small, Screen, write_hud_line, write_message};
+use crate::utility::{clear_console, clock_ms, create_clock, create_clock_at, enable_mouse, pause_clock, reports_key_releases, resume_clock, restore_console, setup_console};
+
+const FRAME_MS : u64 = 15; //how often the screen is refreshed while an animation runs
+const CLOCK_MS : u64 = 50; //how often a running clock on the HUD is refreshed
+const SEEK_MS : f64 = 5000.0; //how far a replay jumps back or ahead
+const MIN_SPEED : f64 = 0.25;
+const MAX_SPEED : f64 = 16.0;
+
+//how a game in the terminal starts and what it writes when it ends
+#[derive(Default)]
+pub struct PlayOptions {
+    pub(crate) fumen: bool, //print the game as a fumen at the end
+    pub(crate) resume: Option<SavedGame>, //continue a saved game, played with the mode, rules and seed it was saved with
+    pub(crate) record: bool, //save a replay of every game
+    pub(crate) race: Option<Race>, //a sprint replay to race against
+}
+
+//the ghost of a race, on a board of its own right of the live one
+struct GhostBoard {
+    race: Race,
+    screen: Screen,
+    animations: Animations,
+}
+
+fn redraw(game: &Game, screen: &Screen, animations: &Animations, ghost: &Option<GhostBoard>, now: u64) {
+    if screen.too_small {
+        match ghost {
+            Some(ghost) => print_too_small(&[&game.field, &ghost.race.ghost.game.field]),
+            None => print_too_small(&[&game.field]),
+        }
+        return;
+    }
+
+    clear_console();
+
+    match ghost {
+        Some(ghost) => {
+            print_board(game, screen, animations, now, race_hud(&ghost.race, game));
+            print_board(&ghost.race.ghost.game, &ghost.screen, &ghost.animations, now, vec![]);
+            write_message(&ghost.screen, &ghost.race.ghost.game.field, 1, "Ghost");
+        },
+        None => print_board(game, screen, animations, now, vec![]),
+    }
+}
+
+//the field with the brick and the HUD, followed by the given lines
+fn print_board(game: &Game, screen: &Screen, animations: &Animations, now: u64, more_hud: Vec<String>) {
+    if game.state == State::Descend && screen.ghost && game.board == BoardVisibility::Visible { //where it lands would give the hidden stack away
+        print_ghost(&game.brick, &find_drop_position(&game.brick, &game.master_node_position, &game.field), screen);
+    }
+
+    if game.state == State::Descend {
+        print_brick(&game.brick, &game.master_node_position, screen);
+    }
+
+    print_field(&game.field, screen, &|brick| is_brick_visible(game, brick));
+    print_animations(animations, &game.field, screen, now);
+    let mut hud = game.mode.hud(game);
+
+    if let Some(held) = &game.held_brick {
+        hud.push(format!("Hold: {}", held.kind));
+    }
+
+    hud.extend(more_hud);
+
+    for (row, line) in hud.iter().enumerate() {
+        write_hud_line(screen, &game.field, row as i32, line);
+    }
+}
+
+//centers the board, and the one of the ghost beside it
+fn fit_boards(game: &Game, screen: &mut Screen, ghost: &mut Option<GhostBoard>) {
+    match ghost {
+        Some(ghost) => {
+            fit_screen_to_terminal(screen, &[&game.field, &ghost.race.ghost.game.field]);
+            place_beside(&mut ghost.screen, screen, &game.field);
+        },
+        None => fit_screen_to_terminal(screen, &[&game.field]),
+    }
+}
+
+fn print_paused(game: &Game, screen: &Screen) {
+    if !screen.too_small {
+        write_message(screen, &game.field, 1, "Paused");
+        write_message(screen, &game.field, 2, "Press any key to resume");
+    }
+}
+
+fn is_key_press(event: &InputEvent) -> bool {
+    matches!(event, InputEvent::Action(ActionEvent { state: KeyState::Pressed, .. }) | InputEvent::Other)
+}
+
+pub fn new_game(config: &Config, seed: Option<u64>, mode: Arc<dyn GameMode>) -> Game {
+    let rules = &config.rules;
+    let seed = seed.unwrap_or_else(rand::random);
+    let scale = if rules.big { 2 } else { 1 };
+    let (width, height) = mode.field_size(rules.width, rules.height);
+    let mut game = create_game(width * scale + 2, height * scale, seed); //the field includes the side borders
+    game.scale = scale;
+    game.master_node_position.x = 1 + (width as i32 - 1) / 2 * scale as i32; //on a column a big brick can reach
+    game.level = rules.level;
+    game.interval = level_interval(rules.level);
+    game.gravity = rules.gravity;
+    game.decay = rules.decay;
+    game.messiness = rules.garbage_messiness;
+    game.clear_gravity = rules.clear_gravity;
+    game.board = rules.board;
+    game.fade_ms = rules.fade_ms;
+    game.randomizer = rules.randomizer;
+    game.rotation = rules.rotation;
+    game.pieces = Arc::new(load_piece_set(&rules.pieces).expect("the piece set was checked with the configuration"));
+    game.das = config.handling.das;
+    game.arr = config.handling.arr;
+    game.lock_delay = config.handling.lock_delay;
+    game.line_clear_delay = line_clear_delay(&config.visuals.animations);
+    game.mode = mode.clone();
+    mode.setup(&mut game);
+
+    return game;
+}
+
+//saves the replay of a recorded game, and says where
+fn keep_replay(game: &Game, config: &Config, start: Option<SavedGame>) -> Option<String> {
+    game.inputs.as_ref()?;
+
+    return Some(match save_replay(&recorded_replay(game, &config.rules, start)) {
+        Ok(path) => format!("Replay saved to {}", path.display()),
+        Err(err) => format!("Cannot save the replay: {}", err),
+    });
+}
+
+//adds a finished game to its high score table and prints the table with the game highlighted
+fn keep_score(game: &Game, config: &Config) {
+    let entry = match game_score(game, &config.rules, &player_name(&config.session)) {
+        Some(entry) => entry,
+        None => return,
+    };
+    let (mode, settings) = (entry.mode, entry.settings.clone());
+
+    match record_score(entry) {
+        Ok((table, rank)) => {
+            println!("Rank {} of {}", rank + 1, table.len());
+            print!("{}", render_table(mode, &settings, &table, Some(rank), TABLE_ROWS));
+        },
+        Err(err) => eprintln!("Cannot save the score: {}", err),
+    }
+}
+
+fn dispatch_events(game: &mut Game, animations: &mut Animations, bot: &Option<Sender<Game>>, now: u64) {
+    for event in game.events.clone().iter() {
+        start_animation(animations, event, now);
+
+        if let (GameEvent::Spawned, Some(bot)) = (event, bot) {
+            bot.send(game.clone()).ok();
+        }
+    }
+
+    game.events.clear();
+}
+
+//puts the saved board of a mode which keeps one into a new game; returns the restored game for the replay to start from
+fn restore_mode_session(game: &mut Game, rules: &Rules) -> Result<Option<SavedGame>, String> {
+    if !game.mode.keeps_session() {
+        return Ok(None);
+    }
+
+    match load_session(game.mode.name()) {
+        Ok(Some(session)) => {
+            restore_session(game, session);
+            return Ok(Some(saved_game(game, rules)));
+        },
+        Ok(None) => return Ok(None),
+        Err(err) => return Err(format!("Cannot resume the session, starting anew: {}", err)),
+    }
+}
+
+//brings the game to the time of the event, then presses or releases its action; sources may deliver their events late
+pub fn apply_input(game: &mut Game, event: ActionEvent) -> bool {
+    let changed = update_game(game, event.time);
+
+    match event.state {
+        KeyState::Pressed => return press_action(game, event.action) || changed,
+        KeyState::Released => {
+            release_action(game, event.action);
+            return changed;
+        },
+    }
+}
+
+//runs a game on the terminal until it ends or the player quits; a bot gets a copy of the game for every new brick
+pub fn play_in_terminal(config: &Config, seed: Option<u64>, mode: Arc<dyn GameMode>, screen: &mut Screen, input: &mut dyn InputSource, bot: Option<Sender<Game>>, options: PlayOptions) {
+    let mut game = new_game(config, seed, mode.clone());
+    game.locks = Some(vec![]).filter(|_| options.fumen);
+    game.inputs = Some(vec![]).filter(|_| options.record);
+    screen.theme = build_theme(config, &game.pieces).expect("the theme was checked with the configuration"); //a mode may bring its own pieces
+    let mut animations = create_animations(config.visuals.animations.clone());
+    let mut session_error = None; //shown once the terminal is restored
+    let mut save_message = None;
+    let mut replay_message = None;
+    let mut start = options.resume.clone(); //where the replay of a resumed game starts
+    let resumed = options.resume.is_some();
+    let mut ghost = options.race.map(|race| GhostBoard {
+        screen: Screen{theme: build_theme(&race.ghost.config, &race.ghost.game.pieces).expect("the theme was checked with the configuration"), ..screen.clone()},
+        animations: create_animations(race.ghost.config.visuals.animations.clone()),
+        race,
+    });
+
+    if let Some(saved) = options.resume {
+        resume_game(&mut game, saved);
+    }
+    else {
+        match restore_mode_session(&mut game, &config.rules) {
+            Ok(restored) => start = restored,
+            Err(err) => session_error = Some(err),
+        }
+    }
+
+    let mut paused = resumed; //set when the terminal loses focus or on pause, cleared by the next key press
+    let mut last_frame = 0;
+    let mut animating = false;
+
+    setup_console();
+    game.auto_repeat = reports_key_releases();
+    let mut clock = create_clock_at(game.time); //setting up the console may wait for the terminal
+
+    if config.handling.mouse {
+        enable_mouse();
+    }
+
+    fit_boards(&game, screen, &mut ghost);
+    redraw(&game, screen, &animations, &ghost, game.time);
+
+    if screen.too_small || paused {
+        pause_clock(&mut clock);
+    }
+
+    if paused {
+        print_paused(&game, screen); //a resumed game waits for the player
+    }
+
+    if resumed {
+        //only once the game is on the screen, so that a failure on the way does not lose the save; quitting may save it again
+        if let Err(err) = remove_saved_game() {
+            save_message = Some(format!("Cannot remove the resumed game, it would be resumed again: {}", err));
+        }
+    }
+
+    loop {
+        // brick handling
+        let now = clock_ms(&clock);
+        let mut changed = update_game(&mut game, now);
+        dispatch_events(&mut game, &mut animations, &bot, now);
+
+        if game.state == State::Stop && !is_topping_out(&animations, now) {
+            redraw(&game, screen, &animations, &ghost, now);
+            let summary = mode.summary(&game);
+
+            for (line, text) in summary.iter().enumerate() {
+                write_message(screen, &game.field, line as i32 + 1, text);
+            }
+
+            write_message(screen, &game.field, summary.len() as i32 + 1, "Press any key to continue...");
+
+            loop {
+                match input.poll(now, Duration::from_millis(100)) {
+                    Some(event) if is_key_press(&event) => break,
+                    Some(InputEvent::Closed) => break,
+                    _ => {}
+                }
+            }
+
+            restore_console();
+            break;
+        }
+
+        // steering handling
+        if let Some(event) = input.poll(now, Duration::from_millis(5)) {
+            if paused && is_key_press(&event) {
+                paused = false; //the key only resumes the game, it does not steer the brick
+
+                if !screen.too_small {
+                    resume_clock(&mut clock);
+                }
+
+                redraw(&game, screen, &animations, &ghost, now);
+                continue;
+            }
+
+            match event {
+                InputEvent::Action(ActionEvent { action, state: KeyState::Pressed, .. }) if screen.too_small && action != Action::Pause && action != Action::Quit => {}, //the brick cannot be seen, releases still go through so that no key stays held
+
+                InputEvent::Action(ActionEvent { action: Action::Restart, state: KeyState::Pressed, .. }) => {
+                    replay_message = keep_replay(&game, config, start.take());
+                    game = new_game(config, seed, mode.clone());
+                    game.locks = Some(vec![]).filter(|_| options.fumen);
+                    game.inputs = Some(vec![]).filter(|_| options.record);
+                    game.auto_repeat = reports_key_releases();
+
+                    match restore_mode_session(&mut game, &config.rules) {
+                        Ok(restored) => start = restored,
+                        Err(err) => session_error = Some(err),
+                    }
+
+                    animations = create_animations(config.visuals.animations.clone());
+                    clock = create_clock();
+
+                    if let Some(ghost) = ghost.as_mut() {
+                        ghost.animations = create_animations(ghost.race.ghost.config.visuals.animations.clone());
+                    }
+                    changed = true;
+                },
+
+                InputEvent::Action(ActionEvent { action: Action::Pause, state: KeyState::Pressed, .. }) | InputEvent::FocusLost => {
+                    paused = true;
+                    if let Some(shift) = game.shift {
+                        release_action(&mut game, shift.action); //keys released while unfocused are never reported
+                    }
+                    pause_clock(&mut clock);
+                    print_paused(&game, screen);
+                },
+
+                InputEvent::Action(ActionEvent { action: Action::Quit, state: KeyState::Pressed, .. }) | InputEvent::Closed => {
+                    write_message(screen, &game.field, 1, "Received abort");
+
+                    if mode.keeps_session() {
+                        if let Err(err) = save_session(&game) {
+                            session_error = Some(format!("Cannot save the session: {}", err));
+                        }
+                    }
+
+                    if config.session.save_on_quit && game.outcome == Outcome::Playing {
+                        save_message = Some(match save_game(&game, &config.rules) {
+                            Ok(_) => String::from("Game saved, continue it with: tetris play --resume"),
+                            Err(err) => format!("Cannot save the game: {}", err),
+                        });
+                    }
+
+                    restore_console();
+                    break;
+                },
+
+                InputEvent::Action(event) => changed |= apply_input(&mut game, event),
+
+                InputEvent::PointerMoved { column } => {
+                    if !paused && !screen.too_small {
+                        changed |= move_brick_to_column(&mut game, column as i32 - screen.origin.x);
+                    }
+                },
+
+                InputEvent::Resize => {
+                    fit_boards(&game, screen, &mut ghost);
+
+                    if screen.too_small {
+                        pause_clock(&mut clock); //the game holds while the board cannot be seen
+                    }
+                    else if !paused {
+                        resume_clock(&mut clock);
+                    }
+
+                    redraw(&game, screen, &animations, &ghost, now);
+
+                    if paused {
+                        print_paused(&game, screen);
+                    }
+                },
+
+                InputEvent::Other => {}
+            }
+        }
+
+        let now = clock_ms(&clock);
+        changed |= update_game(&mut game, now); //a hard drop lands right away
+        dispatch_events(&mut game, &mut animations, &bot, now);
+
+        if let Some(ghost) = ghost.as_mut() {
+            update_race(&mut ghost.race, &game);
+            dispatch_events(&mut ghost.race.ghost.game, &mut ghost.animations, &None, now);
+        }
+
+        let was_animating = animating;
+        animating = is_animating(&animations, now) || ghost.as_ref().is_some_and(|ghost| is_animating(&ghost.animations, now));
+        let animation_frame_due = (animating && now >= last_frame + FRAME_MS) || was_animating != animating;
+        let clock_due = (mode.is_timed() || game.board == BoardVisibility::Fading) && now >= last_frame + CLOCK_MS; //fading bricks go away in between moves
+
+        if !paused && (changed || animation_frame_due || clock_due) {
+            redraw(&game, screen, &animations, &ghost, now);
+            last_frame = now;
+        }
+    }
+
+    replay_message = keep_replay(&game, config, start).or(replay_message);
+
+    if game.state == State::Stop && bot.is_none() {
+        keep_score(&game, config);
+    }
+
+    if let Some(err) = session_error {
+        eprintln!("{}", err);
+    }
+
+    if let Some(message) = replay_message {
+        eprintln!("{}", message);
+    }
+
+    if let Some(message) = save_message {
+        eprintln!("{}", message);
+    }
+
+    if let Some(locks) = &game.locks {
+        match game_pages(locks, &game.field) {
+            Ok(pages) => println!("{}", encode_fumen(&pages)),
+            Err(err) => eprintln!("Cannot write the game as a fumen: {}", err),
+        }
+    }
+}
+
+fn print_playback(playback: &Playback, screen: &Screen, animations: &Animations, speed: f64, paused: bool, hint: &[String]) {
+    let game = &playback.game;
+    redraw(game, screen, animations, &None, game.time);
+
+    if screen.too_small {
+        return;
+    }
+
+    let state = if game.time >= playback.replay.duration { "  end" } else if paused { "  paused" } else { "" };
+    write_message(screen, &game.field, 1, &format!("Replay {} / {}  x{}{}", format_time(game.time), format_time(playback.replay.duration), speed, state));
+    for (line, text) in hint.iter().enumerate() {
+        write_message(screen, &game.field, line as i32 + 2, text);
+    }
+}
+
+//plays a replay back on the terminal through the engine, with pause, seek and speed controls
+pub fn watch_replay(mut playback: Playback, screen: &mut Screen, input: &mut dyn InputSource, hint: &[String]) {
+    screen.theme = build_theme(&playback.config, &playback.game.pieces).expect("the theme was checked with the configuration");
+    let mut animations = create_animations(playback.config.visuals.animations.clone());
+    let mut paused = false;
+    let mut speed = 1.0;
+    let mut position = 0.0; //game time of the replay, in between whole ms at slow speeds
+    let mut last_frame = 0;
+    let clock = create_clock();
+    let mut last_tick = 0;
+
+    setup_console();
+    fit_screen_to_terminal(screen, &[&playback.game.field]);
+    print_playback(&playback, screen, &animations, speed, paused, hint);
+
+    loop {
+        let tick = clock_ms(&clock);
+        let mut changed = false;
+
+        if !paused && position < playback.replay.duration as f64 {
+            position = (position + (tick - last_tick) as f64 * speed).min(playback.replay.duration as f64);
+            seek(&mut playback, position as u64);
+            changed = position as u64 >= playback.replay.duration;
+        }
+
+        last_tick = tick;
+        let now = playback.game.time;
+        dispatch_events(&mut playback.game, &mut animations, &None, now);
+
+        if let Some(event) = input.poll(now, Duration::from_millis(5)) {
+            let jump = match event {
+                InputEvent::Action(ActionEvent { state: KeyState::Released, .. }) => None,
+                InputEvent::Action(ActionEvent { action, .. }) => match action {
+                    Action::Pause => {
+                        paused = !paused;
+                        None
+                    },
+                    Action::MoveLeft => Some(position - SEEK_MS),
+                    Action::MoveRight => Some(position + SEEK_MS),
+                    Action::RotateCw => {
+                        speed = (speed * 2.0).min(MAX_SPEED);
+                        None
+                    },
+                    Action::SoftDrop => {
+                        speed = (speed / 2.0).max(MIN_SPEED);
+                        None
+                    },
+                    Action::Restart => Some(0.0),
+                    Action::Quit => break,
+                    _ => None
+                },
+                InputEvent::Resize => {
+                    fit_screen_to_terminal(screen, &[&playback.game.field]);
+                    None
+                },
+                InputEvent::Closed => break,
+                _ => None
+            };
+
+            if let Some(time) = jump {
+                position = time.clamp(0.0, playback.replay.duration as f64);
+                seek(&mut playback, position as u64);
+                playback.game.events.clear(); //the animations of skipped moments would all start at once
+                animations = create_animations(playback.config.visuals.animations.clone());
+            }
+
+            changed = true;
+        }
+
+        let now = playback.game.time;
+        let animation_frame_due = is_animating(&animations, now) && now >= last_frame + FRAME_MS;
+
+        if changed || animation_frame_due || (!paused && now >= last_frame + CLOCK_MS) {
+            print_playback(&playback, screen, &animations, speed, paused, hint);
+            last_frame = now;
+        }
+    }
+
+    restore_console();
+}
+
+//lets the bot place bricks as fast as it can, without gravity and without a screen
+pub fn run_benchmark(config: &Config, seed: Option<u64>, pieces: u32, print_board: bool) {
+    let mut config = config.clone();
+    config.visuals.animations = AnimationSettings{enabled: false, ..AnimationSettings::default()};
+    let mut game = new_game(&config, seed, create_mode(ModeName::Classic, &ModeArgs::default()));
+    let start = Instant::now();
+    let mut placed = 0;
+
+    update_game(&mut game, 0);
+
+    while placed < pieces && game.state != State::Stop {
+        for action in plan_placement(&game).iter() {
+            apply_action(&mut game, *action);
+        }
+
+        let now = game.time;
+        update_game(&mut game, now);
+        game.events.clear();
+        placed += 1;
+    }
+
+    let elapsed = start.elapsed();
+    println!("Placed {} bricks in {:.2?} ({:.0} bricks/s), cleared {} rows{}",
+             placed, elapsed, placed as f64 / elapsed.as_secs_f64().max(1e-9), game.lines,
+             if game.state == State::Stop { ", topped out" } else { "" });
+    println!("Seed: {}, pieces: {}", game.seed, game.pieces.name);
+
+    if print_board {
+        print!("{}", render_board(&game_board(&game)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs};
+    use crate::board::field_grid;
+    use crate::game::hard_drop;
+
+    #[test]
+    fn a_restarted_zen_game_goes_on_from_the_saved_board() {
+        let dir = env::temp_dir().join(format!("tetris-session-test-{}", std::process::id()));
+        env::set_var("XDG_DATA_HOME", &dir);
+        let mut config = Config::default();
+        config.rules.width = 10;
+        config.rules.height = 20;
+        config.rules.pieces = String::from("tetrominoes");
+
+        let mut game = new_game(&config, Some(4), create_mode(ModeName::Zen, &ModeArgs::default()));
+        for step in 0..3u64 {
+            update_game(&mut game, step * 1000);
+            move_brick_to_column(&mut game, 1 + step as i32 * 4);
+            hard_drop(&mut game);
+        }
+        update_game(&mut game, 5000);
+        save_session(&game).unwrap();
+
+        //what the restart does
+        let mut restarted = new_game(&config, Some(4), create_mode(ModeName::Zen, &ModeArgs::default()));
+        let start = restore_mode_session(&mut restarted, &config.rules);
+        let mut marathon = new_game(&config, Some(4), create_mode(ModeName::Marathon, &ModeArgs::default()));
+        let other = restore_mode_session(&mut marathon, &config.rules);
+        env::remove_var("XDG_DATA_HOME");
+        fs::remove_dir_all(&dir).unwrap();
+
+        let start = start.unwrap().unwrap();
+        assert_eq!(restarted.field.bricks.len(), 3);
+        assert_eq!(field_grid(&restarted.field), field_grid(&game.field));
+        assert_eq!(field_grid(&start.field), field_grid(&game.field)); //the replay starts from the restored board
+        assert_eq!(restarted.score, game.score);
+        assert!(other.is_ok_and(|start| start.is_none()) && marathon.field.bricks.is_empty());
+    }
+}
@@ -1,6 +1,7 @@
 use num_traits::abs;
+use serde::{Deserialize, Serialize};
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Deserialize, Serialize)]
 pub(crate) struct Point{
     pub(crate) x: i32, //position on x-axis
     pub(crate) y: i32 //position on y-axis
@@ -18,13 +19,14 @@ pub fn translate_by(point: &mut Point, ref_point : &Point){
 }
 
 pub fn translate_to_screen(point: &Point, ref_point : &Point) -> Point{
-    let mut new_point = point.clone();
+    let mut new_point = *point;
     new_point.x += ref_point.x;
     new_point.y = ref_point.y - point.y;
 
     return new_point;
 }
 
+//whether the cells share an edge; a cell does not touch itself
 pub fn are_touching(p1 : &Point, p2 : &Point) -> bool {
-    (p1.y == p2.y && abs(p1.x - p2.x) <= 1) || (p1.x == p2.x && abs(p1.y - p2.y) <= 1)
+    abs(p1.x - p2.x) + abs(p1.y - p2.y) == 1
 }
@@ -0,0 +1,238 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use serde::Deserialize;
+
+use crate::board::parse_board;
+use crate::config::config_dir;
+use crate::field::find_full_rows;
+use crate::pieces::{load_piece_set, PieceSet};
+use crate::point::Point;
+
+pub const PUZZLE_NAMES : [&str; 4] = ["tetris", "perfect-clear", "t-spin-triple", "survive"];
+
+const DEFAULT_PIECES : &str = "tetrominoes";
+const DEFAULT_HEIGHT : u16 = 20;
+const SPAWN_ROWS : usize = 2; //rows above the board kept free for the pieces to spawn
+
+#[derive(Copy, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Goal {
+    Lines(u32), //clear this many lines
+    PerfectClear, //clear every cell of the field at once
+    TSpinTriple, //clear three lines with a T turned into its slot
+    Survive(u32), //place this many pieces without topping out
+}
+
+//a puzzle file, see puzzles/*.toml
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PuzzleFile{
+    name : String,
+    goal : Goal,
+    pieces : Option<String>, //the piece set, tetrominoes when missing
+    sequence : String, //the piece letters in the order they come
+    height : Option<u16>, //of the field, the board is as wide as its rows
+    board : String, //the bottom rows of the field in the text board format, without a header
+}
+
+#[derive(Clone)]
+pub struct Puzzle{
+    pub(crate) name : String,
+    pub(crate) goal : Goal,
+    pub(crate) pieces : Arc<PieceSet>,
+    pub(crate) sequence : Vec<usize>, //indices into the piece set, first piece first
+    pub(crate) width : u16,
+    pub(crate) height : u16,
+    pub(crate) cells : Vec<(Point, char)>, //taken cells of the board, x from the left and y from the bottom row
+    pub(crate) text : String, //the puzzle file, kept with a saved game
+}
+
+fn builtin_puzzle(name : &str) -> Option<&'static str>{
+    match name {
+        "tetris" => Some(include_str!("../puzzles/tetris.toml")),
+        "perfect-clear" => Some(include_str!("../puzzles/perfect-clear.toml")),
+        "t-spin-triple" => Some(include_str!("../puzzles/t-spin-triple.toml")),
+        "survive" => Some(include_str!("../puzzles/survive.toml")),
+        _ => None
+    }
+}
+
+//what the player has to do, e.g. "clear 4 lines"
+pub fn describe_goal(goal : Goal) -> String{
+    match goal {
+        Goal::Lines(lines) => format!("clear {} lines", lines),
+        Goal::PerfectClear => String::from("clear the whole board"),
+        Goal::TSpinTriple => String::from("clear three lines with a T-spin"),
+        Goal::Survive(pieces) => format!("place {} pieces without topping out", pieces),
+    }
+}
+
+fn check_goal(goal : Goal, pieces : &PieceSet, sequence : &[usize]) -> Result<(), String>{
+    match goal {
+        Goal::Lines(0) | Goal::Survive(0) => Err(String::from("goal: the count must be at least 1")),
+        Goal::Survive(count) if count as usize > sequence.len() => {
+            Err(format!("goal: {} pieces to survive, but the sequence has only {}", count, sequence.len()))
+        },
+        Goal::TSpinTriple if !sequence.iter().any(|idx| pieces.bricks[*idx].kind == 'T') => {
+            Err(String::from("goal: a T-spin triple needs a T in the sequence"))
+        },
+        _ => Ok(())
+    }
+}
+
+pub fn parse_puzzle(text : &str) -> Result<Puzzle, String>{
+    let file : PuzzleFile = toml::from_str(text).map_err(|err| err.to_string().trim_end().replace('\n', " "))?;
+    let pieces = load_piece_set(file.pieces.as_deref().unwrap_or(DEFAULT_PIECES))?;
+
+    let mut sequence : Vec<usize> = vec![];
+
+    for letter in file.sequence.chars().filter(|letter| !letter.is_whitespace()) {
+        match pieces.bricks.iter().position(|brick| brick.kind == letter) {
+            Some(idx) => sequence.push(idx),
+            None => return Err(format!("sequence: the piece set {} has no piece '{}'", pieces.name, letter))
+        }
+    }
+
+    if sequence.is_empty() {
+        return Err(String::from("sequence: no pieces"));
+    }
+
+    check_goal(file.goal, &pieces, &sequence)?;
+
+    let board = parse_board(&file.board).map_err(|err| format!("board: {}", err))?;
+
+    if board.active.is_some() || board.hold.is_some() || !board.queue.is_empty() {
+        return Err(String::from("board: the pieces of a puzzle come from its sequence, not from the board header"));
+    }
+
+    if let Some(row) = find_full_rows(&board.field).last() {
+        return Err(format!("board: row {} is already full", row + 1));
+    }
+
+    let rows = board.field.height as usize;
+    let height = file.height.unwrap_or(DEFAULT_HEIGHT);
+
+    if !(4..=40).contains(&height) {
+        return Err(format!("height: must be between 4 and 40, got {}", height));
+    }
+
+    if rows + SPAWN_ROWS > height as usize {
+        return Err(format!("board: {} rows leave no room for the pieces in a field {} rows high", rows, height));
+    }
+
+    let cells : Vec<(Point, char)> = board.field.bricks.iter()
+        .flat_map(|brick| brick.vertices.iter().map(|vertex| (Point{x: vertex.x - 1, y: rows as i32 - 1 - vertex.y}, brick.kind)))
+        .collect();
+
+    return Ok(Puzzle{name: file.name, goal: file.goal, pieces: Arc::new(pieces), sequence, width: board.field.width - 2, height, cells, text: String::from(text)});
+}
+
+//~/.config/tetris/puzzles/<name>.toml
+fn puzzle_path(name : &str) -> Option<PathBuf>{
+    Some(config_dir()?.join("puzzles").join(format!("{}.toml", name)))
+}
+
+//a puzzle of the starter pack, one in the puzzles directory of the configuration or a path to a puzzle file
+pub fn load_puzzle(name : &str) -> Result<Puzzle, String>{
+    if let Some(text) = builtin_puzzle(name) {
+        return Ok(parse_puzzle(text).expect("the built-in puzzles are valid"));
+    }
+
+    let path = match puzzle_path(name) {
+        Some(path) if path.exists() => path,
+        _ => PathBuf::from(name)
+    };
+
+    if !path.exists() {
+        return Err(format!("unknown puzzle '{}', expected one of: {}, or a puzzle file", name, PUZZLE_NAMES.join(", ")));
+    }
+
+    let text = fs::read_to_string(&path).map_err(|err| format!("{}: {}", path.display(), err))?;
+
+    return parse_puzzle(&text).map_err(|err| format!("{}: {}", path.display(), err));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::{ModeArgs, ModeName};
+    use crate::config::Config;
+    use crate::game::{hard_drop, hold_brick, move_brick_to_column, rotate_brick, soft_drop, update_game, Game, Outcome};
+    use crate::mode::create_mode;
+    use crate::play::new_game;
+
+    enum Step {
+        Turn(u8), //quarter turns clockwise
+        Column(i32), //field column the brick is centred on
+        SoftDrop, //all the way down, without locking
+        HardDrop,
+        Hold,
+    }
+
+    use Step::{Column, HardDrop, Hold, SoftDrop, Turn};
+
+    fn play(name : &str, steps : &[Step]) -> Game {
+        let args = ModeArgs{puzzle: Some(load_puzzle(name).unwrap()), ..ModeArgs::default()};
+        let mut game = new_game(&Config::default(), Some(1), create_mode(ModeName::Puzzle, &args));
+        game.line_clear_delay = 0;
+        update_game(&mut game, 0);
+
+        for step in steps {
+            match step {
+                Turn(quarter_turns) => assert!(rotate_brick(&mut game, *quarter_turns), "{}: the brick cannot turn", name),
+                Column(column) => { move_brick_to_column(&mut game, *column); },
+                SoftDrop => while soft_drop(&mut game) {},
+                HardDrop => { hard_drop(&mut game); },
+                Hold => assert!(hold_brick(&mut game), "{}: cannot hold", name),
+            }
+
+            let now = game.time;
+            update_game(&mut game, now);
+        }
+
+        return game;
+    }
+
+    #[test]
+    fn every_built_in_puzzle_can_be_solved() {
+        let solutions : [(&str, Vec<Step>); 4] = [
+            ("tetris", vec![Column(1), HardDrop, Turn(1), Column(10), HardDrop]),
+            ("perfect-clear", vec![Hold, Column(9), HardDrop, Turn(2), Column(8), HardDrop]),
+            ("t-spin-triple", vec![Turn(1), Column(3), SoftDrop, Turn(2), HardDrop]),
+            ("survive", vec![
+                Column(9), HardDrop, Turn(1), Column(1), HardDrop, Column(9), HardDrop,
+                Column(3), HardDrop, Column(7), HardDrop, Column(3), HardDrop, Column(7), HardDrop, Column(5), HardDrop,
+            ]),
+        ];
+
+        for (name, steps) in solutions.iter() {
+            let game = play(name, steps);
+
+            assert!(game.outcome == Outcome::Won, "{} is not solved", name);
+        }
+
+        assert_eq!(solutions.len(), PUZZLE_NAMES.len());
+    }
+
+    #[test]
+    fn a_puzzle_is_lost_once_its_pieces_run_out() {
+        let game = play("tetris", &[HardDrop, HardDrop]);
+
+        assert!(game.outcome == Outcome::Lost);
+    }
+
+    #[test]
+    fn a_t_spin_goal_is_not_reached_without_turning() {
+        let game = play("t-spin-triple", &[Turn(1), Column(3), SoftDrop, HardDrop]);
+
+        assert!(game.outcome == Outcome::Lost);
+    }
+
+    #[test]
+    fn a_survive_goal_longer_than_the_sequence_is_rejected() {
+        let text = "name = \"long\"\ngoal = { survive = 3 }\nsequence = \"IO\"\nboard = '''\nG.........\n'''\n";
+
+        assert!(parse_puzzle(text).is_err_and(|err| err.contains("only 2")));
+    }
+}
@@ -0,0 +1,160 @@
+use std::fs;
+use std::path::PathBuf;
+
+use crate::cli::ModeName;
+use crate::config::Config;
+use crate::game::Game;
+use crate::mode::format_time;
+use crate::replay::{load_replay, replays_dir, seek, start_playback, Playback, Replay};
+
+//a live sprint against a recorded one, played back in lockstep
+pub struct Race{
+    pub(crate) ghost : Playback,
+    ghost_splits : Vec<u64>, //game time at which the ghost had cleared 1, 2, 3... lines
+    splits : Vec<u64>, //the same for the live game
+}
+
+//the fastest won sprint among the recorded replays
+pub fn find_best_replay() -> Result<PathBuf, String>{
+    let dir = replays_dir().ok_or("Cannot determine the data directory")?;
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(_) => return Err(String::from("No replays recorded yet, record one with tetris play --record")),
+    };
+    let mut best : Option<(u64, PathBuf)> = None;
+
+    for path in entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()) {
+        let won_at = match load_replay(&path) {
+            Ok(replay) if replay.mode.name == ModeName::Sprint && replay.start.is_none() => replay.won_at,
+            _ => None //other modes, resumed games and files which are not replays
+        };
+
+        if let Some(won_at) = won_at.filter(|won_at| best.as_ref().is_none_or(|(time, _)| won_at < time)) {
+            best = Some((won_at, path));
+        }
+    }
+
+    return best.map(|(_, path)| path).ok_or_else(|| String::from("No finished sprint among the recorded replays"));
+}
+
+pub fn start_race(replay : Replay, config : &Config) -> Result<Race, String>{
+    if replay.mode.name != ModeName::Sprint {
+        return Err(String::from("the ghost has to be a sprint replay"));
+    }
+
+    if replay.start.is_some() {
+        return Err(String::from("the ghost has to be played from the start, not resumed"));
+    }
+
+    let ghost_splits = line_splits(start_playback(replay.clone(), config)?);
+
+    return Ok(Race{ghost: start_playback(replay, config)?, ghost_splits, splits: vec![]});
+}
+
+//plays the whole replay ms by ms to see when each line was cleared
+fn line_splits(mut playback : Playback) -> Vec<u64>{
+    let mut splits : Vec<u64> = vec![];
+
+    for time in 0..=playback.replay.duration {
+        seek(&mut playback, time);
+        playback.game.events.clear();
+
+        while (splits.len() as u32) < playback.game.lines {
+            splits.push(time);
+        }
+    }
+
+    return splits;
+}
+
+//brings the ghost to the time of the live game and notes the lines it cleared
+pub fn update_race(race : &mut Race, game : &Game){
+    if (race.splits.len() as u32) > game.lines {
+        race.splits.clear(); //the game was restarted
+    }
+
+    while (race.splits.len() as u32) < game.lines {
+        race.splits.push(game.time);
+    }
+
+    seek(&mut race.ghost, game.time);
+}
+
+//"-0:01.234" when ahead
+fn format_split(ms : i64) -> String{
+    format!("{}{}", if ms < 0 { '-' } else { '+' }, format_time(ms.unsigned_abs()))
+}
+
+//the HUD lines of the live game: lines ahead of the ghost, and how much sooner or later than the ghost the last line was cleared
+pub fn race_hud(race : &Race, game : &Game) -> Vec<String>{
+    let lines = game.lines as i64 - race.ghost.game.lines as i64;
+    let split = match race.splits.len().checked_sub(1) {
+        Some(last) => match race.ghost_splits.get(last) {
+            Some(ghost) => format_split(race.splits[last] as i64 - *ghost as i64),
+            None => String::from("ghost beaten"), //the ghost never got this far
+        },
+        None => String::from("-"),
+    };
+
+    return vec![format!("Vs ghost: {:+} lines", lines), format!("Split: {}", split)];
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::plan_placement;
+    use crate::cli::ModeArgs;
+    use crate::game::{press_action, release_action, update_game, State};
+    use crate::input::Action;
+    use crate::mode::create_mode;
+    use crate::play::new_game;
+    use crate::replay::recorded_replay;
+
+    #[test]
+    fn the_split_compares_the_times_the_same_line_was_cleared() {
+        let mut config = Config::default();
+        config.rules.width = 10;
+        config.rules.height = 20;
+        config.rules.pieces = String::from("tetrominoes");
+
+        let mut game = new_game(&config, Some(7), create_mode(ModeName::Sprint, &ModeArgs::default()));
+        game.inputs = Some(vec![]);
+        let mut splits : Vec<u64> = vec![];
+        let mut now = 0;
+        update_game(&mut game, now);
+
+        while game.lines < 4 && game.state != State::Stop {
+            //the actions of the bot, then the line clear delay until the next brick shows up
+            let mut steps : Vec<Option<Action>> = plan_placement(&game).into_iter().map(Some).collect();
+            steps.extend([None; 400]);
+
+            for step in steps {
+                now += if step.is_some() { 40 } else { 1 };
+                update_game(&mut game, now);
+
+                if let Some(action) = step {
+                    press_action(&mut game, action);
+                    release_action(&mut game, action);
+                    update_game(&mut game, now); //as the terminal does, a hard drop lands right away
+                }
+
+                while (splits.len() as u32) < game.lines {
+                    splits.push(now);
+                }
+            }
+        }
+
+        let mut race = start_race(recorded_replay(&game, &config.rules, None), &config).unwrap();
+        assert_eq!(race.ghost_splits, splits);
+
+        //a live game which cleared its first line half a second after the ghost
+        let mut live = new_game(&config, Some(7), create_mode(ModeName::Sprint, &ModeArgs::default()));
+        live.lines = 1;
+        live.time = splits[0] + 500;
+        update_race(&mut race, &live);
+        let ghost_lines = splits.iter().filter(|time| **time <= live.time).count();
+
+        assert_eq!(race.ghost.game.lines as usize, ghost_lines);
+        assert_eq!(race_hud(&race, &live), vec![format!("Vs ghost: {:+} lines", 1 - ghost_lines as i64), format!("Split: +{}", format_time(500))]);
+    }
+}
@@ -0,0 +1,316 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+
+use crate::cli::ModeName;
+use crate::config::{data_dir, validate_config, Config, Rules};
+use crate::game::{apply_input, update_game, Game, Input, Outcome};
+use crate::input::Action;
+use crate::mode::create_mode;
+use crate::play::new_game;
+use crate::session::{parse_seed, resume_game, saved_mode, saved_mode_args, SavedGame, SavedMode};
+
+//letters of the recorded actions, upper case for a press and lower case for a release
+const ACTION_LETTERS : [(Action, char); 8] = [
+    (Action::MoveLeft, 'L'),
+    (Action::MoveRight, 'R'),
+    (Action::SoftDrop, 'D'),
+    (Action::HardDrop, 'H'),
+    (Action::RotateCw, 'X'),
+    (Action::RotateCcw, 'Z'),
+    (Action::Rotate180, 'A'),
+    (Action::Hold, 'C'),
+];
+
+//a recorded game: everything the engine needs to play it again, and every input
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Replay{
+    pub(crate) seed : String, //a u64 does not fit into a TOML integer
+    pub(crate) duration : u64, //game time at which the recording ends
+    pub(crate) score : u32, //at the end, to tell the replays apart
+    pub(crate) lines : u32,
+    pub(crate) won_at : Option<u64>, //game time at which the game was won, the time to beat
+    pub(crate) das : u64,
+    pub(crate) arr : u64,
+    pub(crate) lock_delay : u64,
+    pub(crate) line_clear_delay : u64,
+    pub(crate) auto_repeat : bool,
+    pub(crate) inputs : String, //see encode_inputs
+    pub(crate) mode : SavedMode,
+    pub(crate) rules : Rules,
+    pub(crate) start : Option<SavedGame>, //the saved game a resumed game went on from
+}
+
+//a replay on its way through the engine
+pub struct Playback{
+    pub(crate) replay : Replay,
+    pub(crate) config : Config, //with the rules of the replay
+    pub(crate) inputs : Vec<(u64, Input)>,
+    pub(crate) game : Game,
+    pub(crate) next : usize, //the first input not applied yet
+}
+
+fn action_letter(action : Action) -> Option<char>{
+    ACTION_LETTERS.iter().find(|(other, _)| *other == action).map(|(_, letter)| *letter)
+}
+
+//"0X 35x 420H 3h 250@4": ms since the input before, then the action letter or @ and the column the pointer moved the brick to
+pub fn encode_inputs(inputs : &[(u64, Input)]) -> String{
+    let mut tokens : Vec<String> = vec![];
+    let mut last = 0;
+
+    for (time, input) in inputs.iter() {
+        let token = match input {
+            Input::Press(action) => action_letter(*action).map(|letter| letter.to_string()),
+            Input::Release(action) => action_letter(*action).map(|letter| letter.to_ascii_lowercase().to_string()),
+            Input::Column(column) => Some(format!("@{}", column)),
+        };
+
+        if let Some(token) = token {
+            tokens.push(format!("{}{}", time - last, token));
+            last = *time;
+        }
+    }
+
+    return tokens.join(" ");
+}
+
+pub fn decode_inputs(text : &str) -> Result<Vec<(u64, Input)>, String>{
+    let mut inputs : Vec<(u64, Input)> = vec![];
+    let mut time = 0;
+
+    for token in text.split_whitespace() {
+        let split = token.find(|ch : char| !ch.is_ascii_digit()).unwrap_or(token.len());
+        let (delta, rest) = token.split_at(split);
+        let error = || format!("inputs: unexpected '{}'", token);
+        time += delta.parse::<u64>().map_err(|_| error())?;
+
+        let input = match rest.strip_prefix('@') {
+            Some(column) => Input::Column(column.parse().map_err(|_| error())?),
+            None => {
+                let mut letters = rest.chars();
+                let letter = match (letters.next(), letters.next()) {
+                    (Some(letter), None) => letter,
+                    _ => return Err(error())
+                };
+                let (action, _) = ACTION_LETTERS.iter().find(|(_, other)| *other == letter.to_ascii_uppercase()).ok_or_else(error)?;
+
+                if letter.is_ascii_uppercase() { Input::Press(*action) } else { Input::Release(*action) }
+            }
+        };
+
+        inputs.push((time, input));
+    }
+
+    return Ok(inputs);
+}
+
+//the replay of a game recorded from its start, or from the saved game it was resumed from
+pub fn recorded_replay(game : &Game, rules : &Rules, start : Option<SavedGame>) -> Replay{
+    Replay{
+        seed: game.seed.to_string(),
+        duration: game.time,
+        score: game.score,
+        lines: game.lines,
+        won_at: game.ended_at.filter(|_| game.outcome == Outcome::Won),
+        das: game.das,
+        arr: game.arr,
+        lock_delay: game.lock_delay,
+        line_clear_delay: game.line_clear_delay,
+        auto_repeat: game.auto_repeat,
+        inputs: encode_inputs(game.inputs.as_deref().unwrap_or_default()),
+        mode: saved_mode(game.mode.as_ref()),
+        rules: rules.clone(),
+        start,
+    }
+}
+
+//~/.local/share/tetris/replays, one file per game
+pub fn replays_dir() -> Option<PathBuf>{
+    Some(data_dir()?.join("replays"))
+}
+
+//e.g. replays/1760000000-sprint.toml, named by the time the game ended
+pub fn save_replay(replay : &Replay) -> Result<PathBuf, String>{
+    let dir = replays_dir().ok_or("Cannot determine the data directory")?;
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs());
+    let name = create_mode(replay.mode.name, &saved_mode_args(&replay.mode)?).name().to_lowercase();
+    let path = dir.join(format!("{}-{}.toml", seconds, name));
+    let text = toml::to_string(replay).map_err(|err| format!("{}: {}", path.display(), err))?;
+
+    fs::create_dir_all(&dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
+    fs::write(&path, text).map_err(|err| format!("{}: {}", path.display(), err))?;
+
+    return Ok(path);
+}
+
+pub fn load_replay(path : &PathBuf) -> Result<Replay, String>{
+    let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+
+    return toml::from_str(&text).map_err(|err| format!("{}: {}", path.display(), err));
+}
+
+//the game as it was when the recording started
+fn replay_start(replay : &Replay, config : &Config) -> Result<Game, String>{
+    let seed = parse_seed(&replay.seed)?;
+    let args = saved_mode_args(&replay.mode)?;
+    let mut game = new_game(config, Some(seed), create_mode(args.mode.unwrap_or(ModeName::Classic), &args));
+
+    game.das = replay.das;
+    game.arr = replay.arr;
+    game.lock_delay = replay.lock_delay;
+    game.line_clear_delay = replay.line_clear_delay;
+    game.auto_repeat = replay.auto_repeat;
+
+    if let Some(start) = &replay.start {
+        resume_game(&mut game, start.clone());
+    }
+
+    return Ok(game);
+}
+
+pub fn start_playback(replay : Replay, config : &Config) -> Result<Playback, String>{
+    let mut config = config.clone();
+    config.rules = replay.rules.clone();
+    validate_config(&config)?;
+
+    let inputs = decode_inputs(&replay.inputs)?;
+    let game = replay_start(&replay, &config)?;
+
+    return Ok(Playback{replay, config, inputs, game, next: 0});
+}
+
+//plays the replay up to the given game time, from its start again when the time lies behind
+pub fn seek(playback : &mut Playback, time : u64){
+    let time = time.min(playback.replay.duration);
+
+    if time < playback.game.time {
+        playback.game = replay_start(&playback.replay, &playback.config).expect("the replay started before");
+        playback.next = 0;
+    }
+
+    while let Some((at, input)) = playback.inputs.get(playback.next).copied().filter(|(at, _)| *at <= time) {
+        update_game(&mut playback.game, at);
+        apply_input(&mut playback.game, input);
+        playback.next += 1;
+    }
+
+    update_game(&mut playback.game, time);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{game_board, render_board};
+    use crate::cli::ModeArgs;
+    use crate::game::{hard_drop, move_brick_to_column, press_action, release_action, State};
+    use crate::session::{restore_session, saved_game, Session};
+
+    #[test]
+    fn inputs_come_back_unchanged_from_the_compact_text() {
+        let inputs = vec![(0, Input::Press(Action::RotateCw)), (35, Input::Release(Action::RotateCw)), (455, Input::Column(-2)), (455, Input::Press(Action::Hold))];
+        let text = encode_inputs(&inputs);
+
+        assert_eq!(text, "0X 35x 420@-2 0C");
+        assert_eq!(decode_inputs(&text).unwrap(), inputs);
+        assert!(decode_inputs("12Q").is_err_and(|err| err.contains("12Q")));
+    }
+
+    #[test]
+    fn a_replay_plays_the_game_it_recorded() {
+        let mut config = Config::default();
+        config.rules.width = 10;
+        config.rules.height = 20;
+        config.rules.pieces = String::from("tetrominoes");
+        config.handling.lock_delay = 300;
+
+        let mut game = new_game(&config, Some(u64::MAX), create_mode(ModeName::Marathon, &ModeArgs::default()));
+        game.auto_repeat = true;
+        game.inputs = Some(vec![]);
+        let mut now = 0;
+
+        //held moves, pointer moves and plain gravity, with inputs in between the updates of the engine
+        for step in 0..120u64 {
+            now += 37 + step % 5 * 61;
+            update_game(&mut game, now);
+
+            match step % 6 {
+                0 => { press_action(&mut game, Action::MoveLeft); },
+                1 => release_action(&mut game, Action::MoveLeft),
+                2 => { press_action(&mut game, Action::RotateCw); },
+                3 => { move_brick_to_column(&mut game, (step % 9) as i32 + 1); },
+                4 if step % 4 == 0 => { press_action(&mut game, Action::HardDrop); },
+                _ => { press_action(&mut game, Action::SoftDrop); },
+            }
+
+            if game.state == State::Stop {
+                break;
+            }
+        }
+
+        now += 500;
+        update_game(&mut game, now);
+
+        let replay : Replay = toml::from_str(&toml::to_string(&recorded_replay(&game, &config.rules, None)).unwrap()).unwrap();
+        let mut playback = start_playback(replay, &Config::default()).unwrap();
+        seek(&mut playback, now / 2);
+        seek(&mut playback, 1000); //back to an earlier time
+        seek(&mut playback, now);
+
+        assert!(game.placed > 5);
+        assert_eq!(render_board(&game_board(&playback.game)), render_board(&game_board(&game)));
+        assert_eq!((playback.game.score, playback.game.lines, playback.game.placed), (game.score, game.lines, game.placed));
+    }
+
+    #[test]
+    fn a_replay_of_a_restored_session_starts_from_its_board() {
+        let mut config = Config::default();
+        config.rules.width = 10;
+        config.rules.height = 20;
+        config.rules.pieces = String::from("tetrominoes");
+        config.rules.big = true;
+
+        //a big board left over from an earlier run
+        let mut earlier = new_game(&config, Some(5), create_mode(ModeName::Zen, &ModeArgs::default()));
+        for step in 0..4u64 {
+            update_game(&mut earlier, step * 1000);
+            move_brick_to_column(&mut earlier, 1 + step as i32 * 4);
+            hard_drop(&mut earlier);
+        }
+        update_game(&mut earlier, 5000);
+        let session = Session{width: earlier.field.width, height: earlier.field.height, scale: earlier.scale, score: earlier.score, lines: earlier.lines, level: earlier.level, bricks: earlier.field.bricks.clone()};
+
+        //restored as play_in_terminal does it, into a game of rules which are not big any more
+        config.rules.big = false;
+        let mut game = new_game(&config, Some(9), create_mode(ModeName::Zen, &ModeArgs::default()));
+        restore_session(&mut game, session);
+        let start = saved_game(&game, &config.rules);
+        game.inputs = Some(vec![]);
+        let mut now = 0;
+
+        for step in 0..6u64 {
+            now += 700;
+            update_game(&mut game, now);
+            move_brick_to_column(&mut game, (step * 3 % 17) as i32 + 1);
+            press_action(&mut game, Action::HardDrop);
+            update_game(&mut game, now);
+        }
+
+        assert_eq!(game.placed, 6);
+        assert!(game.field.bricks.len() > 6);
+
+        let replay = recorded_replay(&game, &config.rules, Some(start));
+        let mut playback = start_playback(replay, &config).unwrap();
+        seek(&mut playback, now);
+
+        assert_eq!(render_board(&game_board(&playback.game)), render_board(&game_board(&game)));
+        assert_eq!((playback.game.score, playback.game.placed, playback.game.scale), (game.score, game.placed, game.scale));
+
+        //without the restored board the replay plays another game
+        let mut fresh = start_playback(recorded_replay(&game, &config.rules, None), &config).unwrap();
+        seek(&mut fresh, now);
+
+        assert_ne!(render_board(&game_board(&fresh.game)), render_board(&game_board(&game)));
+    }
+}
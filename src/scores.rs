@@ -0,0 +1,263 @@
+use std::cmp::Reverse;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crossterm::style::Stylize;
+use serde::{Deserialize, Serialize};
+
+use crate::cli::{ModeArgs, ModeName};
+use crate::config::{data_dir, Rules, SessionSettings};
+use crate::game::{Game, Outcome};
+use crate::mode::{create_mode, format_time, play_time, Ranking, MODE_NAMES};
+
+pub const TABLE_ROWS : usize = 10;
+
+//a finished game in the high score table
+#[derive(Clone, Deserialize, Serialize)]
+pub struct ScoreEntry{
+    pub(crate) mode : ModeName,
+    pub(crate) settings : String, //see settings_profile; games are only compared with games played the same way
+    pub(crate) player : String,
+    pub(crate) score : u32,
+    pub(crate) lines : u32,
+    pub(crate) level : u32,
+    pub(crate) time : u64, //game time at the end
+    pub(crate) date : u64, //unix seconds
+    pub(crate) seed : String, //a u64 does not fit into a TOML integer
+}
+
+#[derive(Default, Deserialize, Serialize)]
+struct ScoreFile{
+    #[serde(default)]
+    scores : Vec<ScoreEntry>,
+}
+
+//~/.local/share/tetris/scores.toml, every game of every mode
+pub fn scores_path() -> Option<PathBuf>{
+    Some(data_dir()?.join("scores.toml"))
+}
+
+//the name of a setting as the configuration file writes it, e.g. "sticky"
+fn setting_name<T : Serialize>(value : T) -> String{
+    toml::Value::try_from(value).ok().and_then(|value| value.as_str().map(String::from)).unwrap_or_default()
+}
+
+//"10x20, tetrominoes, level 1, bag randomizer": the variant of the mode and the settings which change how a game goes, those left at their defaults omitted
+pub fn settings_profile(game : &Game, rules : &Rules) -> String{
+    let args = game.mode.args();
+    let defaults = Rules::default();
+    let scale = game.scale.max(1);
+    let mut parts : Vec<String> = vec![];
+
+    if args.endless {
+        parts.push(String::from("endless"));
+    }
+
+    if let Some(rows) = args.garbage {
+        parts.push(format!("{} garbage rows", rows));
+    }
+
+    if args.free {
+        parts.push(String::from("free"));
+    }
+
+    if let Some(puzzle) = &args.puzzle {
+        parts.push(puzzle.name.clone());
+    }
+
+    parts.push(format!("{}x{}", (game.field.width - 2) / scale, game.field.height / scale));
+    parts.push(game.pieces.name.clone());
+    parts.push(format!("level {}", rules.level));
+
+    if scale > 1 {
+        parts.push(String::from("big"));
+    }
+
+    if game.board != defaults.board {
+        parts.push(format!("{} board", setting_name(game.board)));
+    }
+
+    if game.gravity != defaults.gravity {
+        parts.push(format!("{} gravity", setting_name(game.gravity)));
+    }
+
+    if game.clear_gravity != defaults.clear_gravity {
+        parts.push(format!("{} clear gravity", setting_name(game.clear_gravity)));
+    }
+
+    if game.randomizer != defaults.randomizer {
+        parts.push(format!("{} randomizer", setting_name(game.randomizer)));
+    }
+
+    if game.rotation != defaults.rotation {
+        parts.push(format!("{} rotation", setting_name(game.rotation)));
+    }
+
+    return parts.join(", ");
+}
+
+//the configured name, or else the login name
+pub fn player_name(session : &SessionSettings) -> String{
+    if !session.player.is_empty() {
+        return session.player.clone();
+    }
+
+    return env::var("USER").or_else(|_| env::var("USERNAME")).unwrap_or_else(|_| String::from("player"));
+}
+
+//None for a game which does not make it into a table, e.g. a sprint which was not finished
+pub fn game_score(game : &Game, rules : &Rules, player : &str) -> Option<ScoreEntry>{
+    if game.mode.ranking() == Ranking::Time && game.outcome != Outcome::Won {
+        return None;
+    }
+
+    return Some(ScoreEntry{
+        mode: game.mode.args().mode.unwrap_or(ModeName::Classic),
+        settings: settings_profile(game, rules),
+        player: String::from(player),
+        score: game.score,
+        lines: game.lines,
+        level: game.level,
+        time: play_time(game),
+        date: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs()),
+        seed: game.seed.to_string(),
+    });
+}
+
+//every recorded game, none when the file does not exist yet
+pub fn load_scores() -> Result<Vec<ScoreEntry>, String>{
+    let path = match scores_path() {
+        Some(path) if path.exists() => path,
+        _ => return Ok(vec![])
+    };
+
+    let text = fs::read_to_string(&path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    let file : ScoreFile = toml::from_str(&text).map_err(|err| format!("{}: {}", path.display(), err))?;
+
+    return Ok(file.scores);
+}
+
+//adds the game to the file, and returns its table with the rank of the game in it
+pub fn record_score(entry : ScoreEntry) -> Result<(Vec<ScoreEntry>, usize), String>{
+    let path = scores_path().ok_or("Cannot determine the data directory")?;
+    let mut scores = load_scores()?;
+    scores.push(entry.clone());
+    let table = ranked_table(&scores, entry.mode, &entry.settings);
+    let rank = new_entry_rank(&table, &entry);
+
+    let text = toml::to_string(&ScoreFile{scores}).map_err(|err| format!("{}: {}", path.display(), err))?;
+    fs::create_dir_all(path.parent().unwrap()).map_err(|err| format!("{}: {}", path.display(), err))?;
+    fs::write(&path, text).map_err(|err| format!("{}: {}", path.display(), err))?;
+
+    return Ok((table, rank));
+}
+
+//the table keeps games as good as the new one ahead of it
+fn new_entry_rank(table : &[ScoreEntry], entry : &ScoreEntry) -> usize{
+    match mode_ranking(entry.mode) {
+        Ranking::Score => table.iter().filter(|other| other.score >= entry.score).count() - 1,
+        Ranking::Time => table.iter().filter(|other| other.time <= entry.time).count() - 1,
+    }
+}
+
+fn mode_ranking(mode : ModeName) -> Ranking{
+    create_mode(mode, &ModeArgs::default()).ranking()
+}
+
+//the games of a mode played with the given settings, best first; equal ones in the order they were played
+pub fn ranked_table(scores : &[ScoreEntry], mode : ModeName, settings : &str) -> Vec<ScoreEntry>{
+    let mut table : Vec<ScoreEntry> = scores.iter().filter(|entry| entry.mode == mode && entry.settings == settings).cloned().collect();
+
+    match mode_ranking(mode) {
+        Ranking::Score => table.sort_by_key(|entry| Reverse(entry.score)),
+        Ranking::Time => table.sort_by_key(|entry| entry.time),
+    }
+
+    return table;
+}
+
+//the mode and settings of every table, in the order of the menu
+pub fn table_keys(scores : &[ScoreEntry]) -> Vec<(ModeName, String)>{
+    let mut keys : Vec<(ModeName, String)> = vec![];
+
+    for mode in MODE_NAMES.iter() {
+        let mut settings : Vec<String> = scores.iter().filter(|entry| entry.mode == *mode).map(|entry| entry.settings.clone()).collect();
+        settings.sort();
+        settings.dedup();
+        keys.extend(settings.into_iter().map(|settings| (*mode, settings)));
+    }
+
+    return keys;
+}
+
+//"2026-10-19 14:03" in UTC
+fn format_date(seconds : u64) -> String{
+    //days since 1970-01-01 to the civil date, after Howard Hinnant's civil_from_days
+    let days = (seconds / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{}-{:02}-{:02} {:02}:{:02}", year, month, day, seconds / 3600 % 24, seconds / 60 % 60)
+}
+
+//the best rows of a table under a heading; the highlighted row stands out, below the others when it did not make it into them
+pub fn render_table(mode : ModeName, settings : &str, table : &[ScoreEntry], highlight : Option<usize>, rows : usize) -> String{
+    let name = create_mode(mode, &ModeArgs::default()).name();
+    let mut lines = vec![format!("{} ({})", name, settings), format!("{:>4}  {:<12} {:>8} {:>6} {:>6} {:>10}  {:<16}  {}", "Rank", "Player", "Score", "Lines", "Level", "Time", "Date", "Seed")];
+
+    for (rank, entry) in table.iter().enumerate() {
+        if rank >= rows {
+            if Some(rank) != highlight {
+                continue;
+            }
+
+            lines.push(String::from("   ..."));
+        }
+
+        let line = format!("{:>4}  {:<12} {:>8} {:>6} {:>6} {:>10}  {:<16}  {}",
+                           rank + 1, entry.player, entry.score, entry.lines, entry.level, format_time(entry.time), format_date(entry.date), entry.seed);
+
+        lines.push(if Some(rank) == highlight { format!("{}", line.reverse()) } else { line });
+    }
+
+    return lines.join("\n") + "\n";
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(mode : ModeName, score : u32, time : u64, date : u64) -> ScoreEntry{
+        ScoreEntry{mode, settings: String::from("10x20, tetrominoes, level 1"), player: String::from("ann"), score, lines: 40, level: 1, time, date, seed: String::from("7")}
+    }
+
+    #[test]
+    fn tables_rank_by_score_or_by_time_and_keep_their_settings_apart() {
+        let mut other = entry(ModeName::Marathon, 9000, 0, 4);
+        other.settings = String::from("10x20, tetrominoes, level 5");
+        let scores = vec![
+            entry(ModeName::Marathon, 1200, 90000, 1),
+            entry(ModeName::Sprint, 0, 61000, 2),
+            entry(ModeName::Marathon, 3400, 60000, 3),
+            other,
+            entry(ModeName::Marathon, 1200, 30000, 5),
+            entry(ModeName::Sprint, 0, 58000, 6),
+        ];
+
+        let marathon = ranked_table(&scores, ModeName::Marathon, "10x20, tetrominoes, level 1");
+        assert_eq!(marathon.iter().map(|entry| entry.date).collect::<Vec<u64>>(), vec![3, 1, 5]);
+
+        let sprint = ranked_table(&scores, ModeName::Sprint, "10x20, tetrominoes, level 1");
+        assert_eq!(sprint.iter().map(|entry| entry.date).collect::<Vec<u64>>(), vec![6, 2]);
+
+        assert_eq!(table_keys(&scores).len(), 3);
+        assert_eq!(format_date(1792385168), "2026-10-19 04:46");
+    }
+}
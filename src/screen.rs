@@ -0,0 +1,127 @@
+use std::io;
+use crossterm::{ExecutableCommand, style, terminal};
+use crossterm::style::Color;
+use serde::{Deserialize, Serialize};
+
+use crate::field::Field;
+use crate::point::Point;
+use crate::theme::Theme;
+use crate::utility::{clear_console, move_to_and_write};
+
+pub const HUD_GAP : u16 = 3; //columns between the right border and the HUD
+pub const HUD_WIDTH : u16 = 20;
+pub const MESSAGE_ROWS : u16 = 4; //rows below the bottom border reserved for messages
+
+#[derive(Copy, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Style {
+    Ascii,
+    Blocks, //needs a font with the unicode block elements
+}
+
+#[derive(Clone)]
+pub struct Glyphs{
+    pub(crate) cell : &'static str,
+    pub(crate) ghost : &'static str,
+    pub(crate) border : &'static str,
+}
+
+pub fn style_glyphs(style : Style) -> Glyphs{
+    match style {
+        Style::Ascii => Glyphs{cell: "*", ghost: ".", border: "+"},
+        Style::Blocks => Glyphs{cell: "█", ghost: "░", border: "▒"},
+    }
+}
+
+#[derive(Clone)]
+pub struct Screen{
+    pub(crate) theme : Theme,
+    pub(crate) glyphs : Glyphs,
+    pub(crate) ghost : bool, //whether the landing position of the brick is shown
+    pub(crate) origin : Point, //terminal position of the board's top-left corner
+    pub(crate) too_small : bool, //nothing but a warning is drawn while the terminal cannot fit the board
+}
+
+pub fn create_screen(theme : Theme, style : Style, ghost : bool) -> Screen{
+    Screen{theme, glyphs: style_glyphs(style), ghost, origin: Point{x: 0, y: 0}, too_small: false}
+}
+
+//the boards side by side, each with its HUD
+pub fn required_size(fields : &[&Field]) -> (u16, u16){
+    let mut size = (0, 0);
+
+    for field in fields.iter() {
+        let board_width = field.width; //borders sit in the first and the last column
+        let board_height = field.height + 1; //bottom border sits below the last row
+
+        size.0 += board_width + HUD_GAP + HUD_WIDTH;
+        size.1 = size.1.max(board_height + MESSAGE_ROWS);
+    }
+
+    return size;
+}
+
+//centers the boards and HUDs in a terminal of the given size; the screen draws the first board
+pub fn fit_screen(screen : &mut Screen, fields : &[&Field], terminal_size : (u16, u16)){
+    let (required_cols, required_rows) = required_size(fields);
+    let (cols, rows) = terminal_size;
+
+    screen.too_small = cols < required_cols || rows < required_rows;
+
+    if !screen.too_small {
+        screen.origin = Point{x: ((cols - required_cols) / 2) as i32, y: ((rows - required_rows) / 2) as i32};
+    }
+}
+
+pub fn fit_screen_to_terminal(screen : &mut Screen, fields : &[&Field]){
+    let terminal_size = terminal::size().unwrap_or((80, 24));
+    fit_screen(screen, fields, terminal_size);
+}
+
+//moves a screen next to the board of another one, e.g. for the ghost of a race
+pub fn place_beside(side : &mut Screen, screen : &Screen, field : &Field){
+    side.origin = Point{x: screen.origin.x + required_size(&[field]).0 as i32, y: screen.origin.y};
+    side.too_small = screen.too_small;
+}
+
+pub fn print_too_small(fields : &[&Field]){
+    let (required_cols, required_rows) = required_size(fields);
+    let (cols, rows) = terminal::size().unwrap_or((0, 0));
+
+    clear_console();
+    move_to_and_write(0, 0, "Terminal too small!");
+    move_to_and_write(0, 1, format!("Needs {}x{}, has {}x{}", required_cols, required_rows, cols, rows).as_str());
+}
+
+//writes relative to the board's top-left corner
+pub fn write_at(screen : &Screen, x : i32, y : i32, msg : &str, color : Color){
+    let mut console = io::stdout();
+
+    console.execute(style::SetForegroundColor(color)).unwrap();
+    move_to_and_write(screen.origin.x + x, screen.origin.y + y, msg);
+    console.execute(style::ResetColor).unwrap();
+}
+
+pub fn write_points(screen : &Screen, points : &[Point], msg : &str, color : Color){
+    let mut console = io::stdout();
+
+    console.execute(style::SetForegroundColor(color)).unwrap();
+
+    for point in points.iter(){
+        move_to_and_write(screen.origin.x + point.x, screen.origin.y + point.y, msg);
+    }
+
+    console.execute(style::ResetColor).unwrap();
+}
+
+pub fn hud_column(field : &Field) -> i32{
+    (field.width - 1 + HUD_GAP) as i32
+}
+
+pub fn write_hud_line(screen : &Screen, field : &Field, row : i32, msg : &str){
+    write_at(screen, hud_column(field), row, msg, screen.theme.hud);
+}
+
+pub fn write_message(screen : &Screen, field : &Field, line : i32, msg : &str){
+    write_at(screen, 0, field.height as i32 + 1 + line, msg, screen.theme.hud);
+}
@@ -0,0 +1,296 @@
+use std::fs;
+use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+
+use crate::brick::{Brick, DeadBrick};
+use crate::cli::{ModeArgs, ModeName};
+use crate::config::{data_dir, Rules};
+use crate::field::Field;
+use crate::game::{Game, Outcome, State};
+use crate::mode::GameMode;
+use crate::point::Point;
+use crate::puzzle::parse_puzzle;
+
+//what is kept of a game between two runs; the active brick and the sequence start anew
+#[derive(Deserialize, Serialize)]
+pub struct Session{
+    pub(crate) width : u16,
+    pub(crate) height : u16,
+    #[serde(default = "default_scale")]
+    pub(crate) scale : u16, //the board keeps the brick size it was played with
+    pub(crate) score : u32,
+    pub(crate) lines : u32,
+    pub(crate) level : u32,
+    pub(crate) bricks : Vec<DeadBrick>,
+}
+
+fn default_scale() -> u16{
+    1
+}
+
+//one session per mode, e.g. ~/.local/share/tetris/sessions/zen.toml
+pub fn session_path(mode_name : &str) -> Option<PathBuf>{
+    Some(data_dir()?.join("sessions").join(format!("{}.toml", mode_name.to_lowercase())))
+}
+
+pub fn save_session(game : &Game) -> Result<PathBuf, String>{
+    let path = session_path(game.mode.name()).ok_or("Cannot determine the data directory")?;
+    let session = Session{
+        width: game.field.width,
+        height: game.field.height,
+        scale: game.scale,
+        score: game.score,
+        lines: game.lines,
+        level: game.level,
+        bricks: game.field.bricks.clone(),
+    };
+
+    let text = toml::to_string(&session).map_err(|err| format!("{}: {}", path.display(), err))?;
+    fs::create_dir_all(path.parent().unwrap()).map_err(|err| format!("{}: {}", path.display(), err))?;
+    fs::write(&path, text).map_err(|err| format!("{}: {}", path.display(), err))?;
+
+    return Ok(path);
+}
+
+//None when the mode has no saved session
+pub fn load_session(mode_name : &str) -> Result<Option<Session>, String>{
+    let path = match session_path(mode_name) {
+        Some(path) if path.exists() => path,
+        _ => return Ok(None)
+    };
+
+    let text = fs::read_to_string(&path).map_err(|err| format!("{}: {}", path.display(), err))?;
+
+    return toml::from_str(&text).map(Some).map_err(|err| format!("{}: {}", path.display(), err));
+}
+
+//puts a saved board and counters into a game which has not started yet
+pub fn restore_session(game : &mut Game, session : Session){
+    game.field = Field{bricks: session.bricks, width: session.width, height: session.height};
+    game.scale = session.scale;
+    game.score = session.score;
+    game.lines = session.lines;
+    game.level = session.level;
+}
+
+//the mode of a saved game, as given on the command line
+#[derive(Clone, Deserialize, Serialize)]
+pub struct SavedMode{
+    pub(crate) name : ModeName,
+    #[serde(default)]
+    pub(crate) endless : bool,
+    pub(crate) garbage : Option<u16>,
+    #[serde(default)]
+    pub(crate) free : bool,
+    pub(crate) puzzle : Option<String>, //the puzzle file
+}
+
+//everything needed to continue a game exactly where it was left; the rest follows from the rules, the mode and the seed
+#[derive(Clone, Deserialize, Serialize)]
+pub struct SavedGame{
+    pub(crate) seed : String, //a u64 does not fit into a TOML integer
+    pub(crate) draws : u64, //position of the brick generator, in 32-bit words
+    pub(crate) garbage_draws : u64,
+    pub(crate) state : State,
+    pub(crate) outcome : Outcome,
+    pub(crate) ended_at : Option<u64>,
+    pub(crate) score : u32,
+    pub(crate) lines : u32,
+    pub(crate) level : u32,
+    pub(crate) interval : u64,
+    pub(crate) placed : u32,
+    pub(crate) spun : bool,
+    pub(crate) t_spin : bool,
+    pub(crate) hold_used : bool,
+    pub(crate) garbage_hole : Option<i32>,
+    pub(crate) bag : Vec<usize>,
+    pub(crate) sequence : Option<Vec<usize>>,
+    pub(crate) lock_deadline : Option<u64>,
+    pub(crate) lock_resets : u32,
+    pub(crate) time : u64,
+    pub(crate) deadline : u64,
+    pub(crate) master_node_position : Point,
+    pub(crate) brick : Brick,
+    pub(crate) spawned_brick : Brick,
+    pub(crate) held_brick : Option<Brick>,
+    pub(crate) field : Field,
+    #[serde(default = "default_scale")]
+    pub(crate) scale : u16, //a restored session keeps its own, whatever the rules say
+    pub(crate) mode : SavedMode,
+    pub(crate) rules : Rules,
+}
+
+//~/.local/share/tetris/save.toml, a single game whatever its mode
+pub fn save_path() -> Option<PathBuf>{
+    Some(data_dir()?.join("save.toml"))
+}
+
+pub fn saved_mode(mode : &dyn GameMode) -> SavedMode{
+    let args = mode.args();
+
+    SavedMode{
+        name: args.mode.unwrap_or(ModeName::Classic),
+        endless: args.endless,
+        garbage: args.garbage,
+        free: args.free,
+        puzzle: args.puzzle.map(|puzzle| puzzle.text),
+    }
+}
+
+pub fn saved_game(game : &Game, rules : &Rules) -> SavedGame{
+    SavedGame{
+        seed: game.seed.to_string(),
+        draws: game.rng.get_word_pos() as u64,
+        garbage_draws: game.garbage_rng.get_word_pos() as u64,
+        state: game.state,
+        outcome: game.outcome,
+        ended_at: game.ended_at,
+        score: game.score,
+        lines: game.lines,
+        level: game.level,
+        interval: game.interval,
+        placed: game.placed,
+        spun: game.spun,
+        t_spin: game.t_spin,
+        hold_used: game.hold_used,
+        garbage_hole: game.garbage_hole,
+        bag: game.bag.clone(),
+        sequence: game.sequence.clone(),
+        lock_deadline: game.lock_deadline,
+        lock_resets: game.lock_resets,
+        time: game.time,
+        deadline: game.deadline,
+        master_node_position: game.master_node_position,
+        brick: game.brick.clone(),
+        spawned_brick: game.spawned_brick.clone(),
+        held_brick: game.held_brick.clone(),
+        field: game.field.clone(),
+        scale: game.scale,
+        mode: saved_mode(game.mode.as_ref()),
+        rules: rules.clone(),
+    }
+}
+
+pub fn save_game(game : &Game, rules : &Rules) -> Result<PathBuf, String>{
+    let path = save_path().ok_or("Cannot determine the data directory")?;
+    let text = toml::to_string(&saved_game(game, rules)).map_err(|err| format!("{}: {}", path.display(), err))?;
+
+    fs::create_dir_all(path.parent().unwrap()).map_err(|err| format!("{}: {}", path.display(), err))?;
+    fs::write(&path, text).map_err(|err| format!("{}: {}", path.display(), err))?;
+
+    return Ok(path);
+}
+
+//None when no game is saved
+pub fn load_saved_game() -> Result<Option<SavedGame>, String>{
+    let path = match save_path() {
+        Some(path) if path.exists() => path,
+        _ => return Ok(None)
+    };
+
+    let text = fs::read_to_string(&path).map_err(|err| format!("{}: {}", path.display(), err))?;
+
+    return toml::from_str(&text).map(Some).map_err(|err| format!("{}: {}", path.display(), err));
+}
+
+//once a saved game is resumed, so that it is not resumed twice
+pub fn remove_saved_game() -> Result<(), String>{
+    match save_path() {
+        Some(path) if path.exists() => fs::remove_file(&path).map_err(|err| format!("{}: {}", path.display(), err)),
+        _ => Ok(())
+    }
+}
+
+pub fn parse_seed(text : &str) -> Result<u64, String>{
+    text.parse().map_err(|_| format!("seed: expected a number, got '{}'", text))
+}
+
+//the saved mode as create_mode takes it
+pub fn saved_mode_args(mode : &SavedMode) -> Result<ModeArgs, String>{
+    let puzzle = match &mode.puzzle {
+        Some(text) => Some(parse_puzzle(text).map_err(|err| format!("mode.puzzle: {}", err))?),
+        None => None
+    };
+
+    return Ok(ModeArgs{mode: Some(mode.name), endless: mode.endless, garbage: mode.garbage, free: mode.free, puzzle});
+}
+
+//puts the saved state into a game created with the saved rules, mode and seed
+pub fn resume_game(game : &mut Game, saved : SavedGame){
+    game.rng.set_word_pos(saved.draws as u128);
+    game.garbage_rng.set_word_pos(saved.garbage_draws as u128);
+    game.state = saved.state;
+    game.outcome = saved.outcome;
+    game.ended_at = saved.ended_at;
+    game.score = saved.score;
+    game.lines = saved.lines;
+    game.level = saved.level;
+    game.interval = saved.interval;
+    game.placed = saved.placed;
+    game.spun = saved.spun;
+    game.t_spin = saved.t_spin;
+    game.hold_used = saved.hold_used;
+    game.garbage_hole = saved.garbage_hole;
+    game.bag = saved.bag;
+    game.sequence = saved.sequence;
+    game.lock_deadline = saved.lock_deadline;
+    game.lock_resets = saved.lock_resets;
+    game.time = saved.time;
+    game.deadline = saved.deadline;
+    game.master_node_position = saved.master_node_position;
+    game.brick = saved.brick;
+    game.spawned_brick = saved.spawned_brick;
+    game.held_brick = saved.held_brick;
+    game.field = saved.field;
+    game.scale = saved.scale;
+    game.shift = None; //the keys are up again
+    game.events.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{game_board, render_board};
+    use crate::config::Config;
+    use crate::game::{add_garbage, hard_drop, hold_brick, move_brick_to_column, rotate_brick, update_game};
+    use crate::mode::create_mode;
+    use crate::play::new_game;
+
+    fn play_a_while(game : &mut Game, columns : &[i32]) {
+        for column in columns {
+            rotate_brick(game, 1);
+            move_brick_to_column(game, *column);
+            hard_drop(game);
+            let now = game.time + 100;
+            update_game(game, now);
+        }
+    }
+
+    #[test]
+    fn a_resumed_game_goes_on_as_the_saved_one_would() {
+        let mut config = Config::default();
+        config.rules.width = 10;
+        config.rules.height = 20;
+        config.rules.pieces = String::from("tetrominoes");
+
+        let args = ModeArgs{garbage: Some(6), ..ModeArgs::default()};
+        let mut game = new_game(&config, Some(u64::MAX - 1), create_mode(ModeName::Dig, &args));
+        update_game(&mut game, 0);
+        play_a_while(&mut game, &[2, 6, 9]);
+        hold_brick(&mut game);
+
+        let saved : SavedGame = toml::from_str(&toml::to_string(&saved_game(&game, &config.rules)).unwrap()).unwrap();
+        let args = saved_mode_args(&saved.mode).unwrap();
+        let mut resumed = new_game(&config, Some(parse_seed(&saved.seed).unwrap()), create_mode(args.mode.unwrap(), &args));
+        resume_game(&mut resumed, saved);
+
+        for game in [&mut game, &mut resumed] {
+            play_a_while(game, &[4, 1, 7, 3]);
+            add_garbage(game, 2);
+            play_a_while(game, &[5, 8]);
+        }
+
+        assert_eq!(render_board(&game_board(&resumed)), render_board(&game_board(&game)));
+        assert_eq!((resumed.score, resumed.lines, resumed.time), (game.score, game.lines, game.time));
+    }
+}
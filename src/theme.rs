@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+use crossterm::style::Color;
+
+#[derive(Clone)]
+pub struct Theme{
+    pub(crate) pieces : HashMap<char, Color>, //piece kind (letter) -> color
+    pub(crate) fallback : Color, //used for kinds that the theme does not know
+    pub(crate) ghost : Color,
+    pub(crate) border : Color,
+    pub(crate) hud : Color,
+}
+
+pub const THEME_NAMES : [&str; 4] = ["guideline", "high-contrast", "colorblind", "monochrome"];
+
+fn pieces_from(colors : [(char, Color); 7]) -> HashMap<char, Color>{
+    colors.into_iter().collect()
+}
+
+pub fn guideline_theme() -> Theme{
+    Theme{
+        pieces: pieces_from([
+            ('I', Color::Rgb { r: 0, g: 240, b: 240 }),
+            ('O', Color::Rgb { r: 240, g: 240, b: 0 }),
+            ('T', Color::Rgb { r: 160, g: 0, b: 240 }),
+            ('S', Color::Rgb { r: 0, g: 240, b: 0 }),
+            ('Z', Color::Rgb { r: 240, g: 0, b: 0 }),
+            ('J', Color::Rgb { r: 0, g: 0, b: 240 }),
+            ('L', Color::Rgb { r: 240, g: 160, b: 0 }),
+        ]),
+        fallback: Color::AnsiValue(250),
+        ghost: Color::AnsiValue(240),
+        border: Color::AnsiValue(245),
+        hud: Color::Reset,
+    }
+}
+
+pub fn high_contrast_theme() -> Theme{
+    Theme{
+        pieces: pieces_from([
+            ('I', Color::Cyan),
+            ('O', Color::Yellow),
+            ('T', Color::Magenta),
+            ('S', Color::Green),
+            ('Z', Color::Red),
+            ('J', Color::Blue),
+            ('L', Color::White),
+        ]),
+        fallback: Color::White,
+        ghost: Color::DarkGrey,
+        border: Color::White,
+        hud: Color::White,
+    }
+}
+
+pub fn colorblind_theme() -> Theme{
+    //Okabe-Ito palette, distinguishable with the common kinds of color vision deficiency
+    Theme{
+        pieces: pieces_from([
+            ('I', Color::Rgb { r: 86, g: 180, b: 233 }),
+            ('O', Color::Rgb { r: 240, g: 228, b: 66 }),
+            ('T', Color::Rgb { r: 204, g: 121, b: 167 }),
+            ('S', Color::Rgb { r: 0, g: 158, b: 115 }),
+            ('Z', Color::Rgb { r: 213, g: 94, b: 0 }),
+            ('J', Color::Rgb { r: 0, g: 114, b: 178 }),
+            ('L', Color::Rgb { r: 230, g: 159, b: 0 }),
+        ]),
+        fallback: Color::Rgb { r: 200, g: 200, b: 200 },
+        ghost: Color::AnsiValue(240),
+        border: Color::AnsiValue(245),
+        hud: Color::Reset,
+    }
+}
+
+pub fn monochrome_theme() -> Theme{
+    Theme{
+        pieces: HashMap::new(),
+        fallback: Color::Reset,
+        ghost: Color::Reset,
+        border: Color::Reset,
+        hud: Color::Reset,
+    }
+}
+
+pub fn find_theme(name : &str) -> Option<Theme>{
+    match name {
+        "guideline" => Some(guideline_theme()),
+        "high-contrast" => Some(high_contrast_theme()),
+        "colorblind" => Some(colorblind_theme()),
+        "monochrome" => Some(monochrome_theme()),
+        _ => None
+    }
+}
+
+//a lower case kind, the mirror image of a piece, takes the color of the upper case one unless it has its own
+pub fn piece_color(theme : &Theme, kind : char) -> Color{
+    match theme.pieces.get(&kind).or_else(|| theme.pieces.get(&kind.to_ascii_uppercase())) {
+        Some(color) => *color,
+        None => theme.fallback
+    }
+}
+
+//accepts color names ("red", "dark_yellow", "default"), 256-color indices ("208") and RGB ("#ff8800")
+pub fn parse_color(text : &str) -> Result<Color, String>{
+    let text = text.trim();
+
+    if let Some(hex) = text.strip_prefix('#') {
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) { //checked before slicing the text by bytes
+            return Err(format!("'{}' is not a #rrggbb color", text));
+        }
+
+        let channel = |idx : usize| u8::from_str_radix(&hex[idx..idx + 2], 16)
+            .map_err(|_| format!("'{}' is not a #rrggbb color", text));
+
+        return Ok(Color::Rgb { r: channel(0)?, g: channel(2)?, b: channel(4)? });
+    }
+
+    if let Ok(index) = text.parse::<u8>() {
+        return Ok(Color::AnsiValue(index));
+    }
+
+    let color = match text.to_lowercase().replace('-', "_").as_str() {
+        "default" | "reset" => Color::Reset,
+        "black" => Color::Black,
+        "dark_grey" | "dark_gray" => Color::DarkGrey,
+        "red" => Color::Red,
+        "dark_red" => Color::DarkRed,
+        "green" => Color::Green,
+        "dark_green" => Color::DarkGreen,
+        "yellow" => Color::Yellow,
+        "dark_yellow" => Color::DarkYellow,
+        "blue" => Color::Blue,
+        "dark_blue" => Color::DarkBlue,
+        "magenta" => Color::Magenta,
+        "dark_magenta" => Color::DarkMagenta,
+        "cyan" => Color::Cyan,
+        "dark_cyan" => Color::DarkCyan,
+        "white" => Color::White,
+        "grey" | "gray" => Color::Grey,
+        _ => return Err(format!("unknown color '{}'", text))
+    };
+
+    return Ok(color);
+}
+
+//applies "piece letter / ghost / border / hud -> color" overrides on top of a theme
+pub fn apply_color_overrides(theme : &mut Theme, overrides : &HashMap<String, String>) -> Result<(), String>{
+    for (target, value) in overrides.iter() {
+        let color = parse_color(value).map_err(|err| format!("visuals.colors.{}: {}", target, err))?;
+
+        match target.as_str() {
+            "ghost" => theme.ghost = color,
+            "border" => theme.border = color,
+            "hud" => theme.hud = color,
+            "fallback" => theme.fallback = color,
+            _ => {
+                let mut letters = target.chars();
+                match (letters.next(), letters.next()) {
+                    (Some(kind), None) => { theme.pieces.insert(kind.to_ascii_uppercase(), color); },
+                    _ => return Err(format!("visuals.colors.{}: expected a piece letter, 'ghost', 'border', 'hud' or 'fallback'", target))
+                }
+            }
+        }
+    }
+
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colors_parse_by_name_index_and_rgb() {
+        assert_eq!(parse_color(" Dark-Yellow "), Ok(Color::DarkYellow));
+        assert_eq!(parse_color("208"), Ok(Color::AnsiValue(208)));
+        assert_eq!(parse_color("#ff8800"), Ok(Color::Rgb { r: 255, g: 136, b: 0 }));
+
+        for text in ["#aébcd", "#ff88", "#+f8800", "#gg8800", "ultraviolet"] {
+            assert!(parse_color(text).is_err(), "{}", text);
+        }
+
+        assert_eq!(parse_color("#aébcd").err(), Some(String::from("'#aébcd' is not a #rrggbb color")));
+    }
+}
@@ -1,75 +1,117 @@
-use std::{io, thread};
+use std::io;
 use std::io::Write;
-use std::sync::mpsc;
-use std::sync::mpsc::Receiver;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 use crossterm::cursor::MoveTo;
-use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, read};
+use crossterm::event;
 use crossterm::{cursor, ExecutableCommand, execute, terminal};
-use num_derive::FromPrimitive;
 
 use crate::point::{Point, translate_by, translate_to_screen};
 
-#[derive(FromPrimitive)]
-pub enum Keys{
-    Up = 1,
-    Right = 2,
-    Left = 3,
-    Abort = 4
-}
-
-pub fn spawn_stdin_channel() -> Receiver<u16> {
-    let (tx, rx) = mpsc::channel::<u16>();
-    thread::spawn(move || loop {
-        match read().unwrap() {
-            Event::Key(KeyEvent { code: KeyCode::Up, modifiers: KeyModifiers::NONE, kind: KeyEventKind::Press, .. }) => tx.send(Keys::Up as u16).unwrap(),
-            Event::Key(KeyEvent { code: KeyCode::Right, modifiers: KeyModifiers::NONE, kind: KeyEventKind::Press, .. }) => tx.send(Keys::Right as u16).unwrap(),
-            Event::Key(KeyEvent { code: KeyCode::Left, modifiers: KeyModifiers::NONE, kind: KeyEventKind::Press, .. }) => tx.send(Keys::Left as u16).unwrap(),
-            Event::Key(KeyEvent { code: KeyCode::Char('c'), modifiers: KeyModifiers::CONTROL, kind: KeyEventKind::Release, .. }) => {
-                tx.send(Keys::Abort as u16).unwrap();
-                break;
-            },
-            _ => continue
-        }
-    });
-
-    return rx
-}
+static KEYBOARD_ENHANCED : AtomicBool = AtomicBool::new(false);
 
-pub fn get_screen_translated_vertices(vertices : &Vec<Point>, master_node_position : &Point) -> Vec<Point>{
+pub fn get_screen_translated_vertices(vertices : &[Point], master_node_position : &Point) -> Vec<Point>{
     let mut translated : Vec<Point> = vec![];
 
     for point in vertices.iter(){
-        let new_point = translate_to_screen(point, &master_node_position);
+        let new_point = translate_to_screen(point, master_node_position);
         translated.push(new_point);
     }
 
     return translated;
 }
 
-pub fn get_translated_vertices(vertices : &Vec<Point>, master_node_position : &Point) -> Vec<Point>{
+pub fn get_translated_vertices(vertices : &[Point], master_node_position : &Point) -> Vec<Point>{
     let mut translated : Vec<Point> = vec![];
 
     for point in vertices.iter(){
-        let mut new_point = point.clone();
-        translate_by(&mut new_point, &master_node_position);
+        let mut new_point = *point;
+        translate_by(&mut new_point, master_node_position);
         translated.push(new_point);
     }
 
     return translated;
 }
 
-pub fn shorten_interval(interval : u128) -> u128{
-    let new_interval : f32 = (interval as f32) * (0.999f32);
-    return new_interval.floor() as u128;
+pub struct GameClock{ //wall clock that stands still while the game is paused
+    start : Instant,
+    paused_at : Option<Instant>,
+    paused_for : Duration,
+    offset : u64, //ms on the clock when it started, e.g. of a resumed game
+}
+
+pub fn create_clock() -> GameClock{
+    create_clock_at(0)
+}
+
+pub fn create_clock_at(ms : u64) -> GameClock{
+    GameClock{start: Instant::now(), paused_at: None, paused_for: Duration::ZERO, offset: ms}
+}
+
+pub fn clock_ms(clock : &GameClock) -> u64{
+    let now = clock.paused_at.unwrap_or_else(Instant::now);
+    return clock.offset + (now - clock.start - clock.paused_for).as_millis() as u64;
+}
+
+pub fn pause_clock(clock : &mut GameClock){
+    if clock.paused_at.is_none() {
+        clock.paused_at = Some(Instant::now());
+    }
+}
+
+pub fn resume_clock(clock : &mut GameClock){
+    if let Some(paused_at) = clock.paused_at.take() {
+        clock.paused_for += paused_at.elapsed();
+    }
+}
+
+pub fn shorten_interval(interval : u64, decay : f64) -> u64{
+    let new_interval : f32 = (interval as f32) * (decay as f32);
+    return new_interval.floor() as u64;
 }
 
 pub fn setup_console(){
+    terminal::enable_raw_mode().expect("terminal::enable_raw_mode failed");
+
     execute!(
         io::stdout(),
         terminal::Clear(terminal::ClearType::All),
         cursor::DisableBlinking,
-        cursor::Hide
-    ).expect("terminal::Clear or Cursor::DisableBlinking or Cursor::Hide failed");
+        cursor::Hide,
+        event::EnableFocusChange
+    ).expect("terminal::Clear or Cursor::DisableBlinking or Cursor::Hide or EnableFocusChange failed");
+
+    if terminal::supports_keyboard_enhancement().unwrap_or(false) { //needed for key release events outside of Windows
+        KEYBOARD_ENHANCED.store(true, Ordering::Relaxed);
+        execute!(
+            io::stdout(),
+            event::PushKeyboardEnhancementFlags(event::KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+        ).expect("PushKeyboardEnhancementFlags failed");
+    }
+}
+
+//without key release events the terminal's own key repeat has to stand in for the game's auto-repeat
+pub fn reports_key_releases() -> bool{
+    cfg!(windows) || KEYBOARD_ENHANCED.load(Ordering::Relaxed)
+}
+
+pub fn enable_mouse(){
+    execute!(io::stdout(), event::EnableMouseCapture).expect("EnableMouseCapture failed");
+}
+
+pub fn restore_console(){
+    if KEYBOARD_ENHANCED.swap(false, Ordering::Relaxed) { //querying the terminal again would race the input thread
+        execute!(io::stdout(), event::PopKeyboardEnhancementFlags).expect("PopKeyboardEnhancementFlags failed");
+    }
+
+    execute!(
+        io::stdout(),
+        event::DisableFocusChange,
+        event::DisableMouseCapture,
+        cursor::Show
+    ).expect("DisableFocusChange or DisableMouseCapture or Cursor::Show failed");
+
+    terminal::disable_raw_mode().expect("terminal::disable_raw_mode failed");
 }
 
 
@@ -85,7 +127,7 @@ pub fn move_to_and_write(mut x : i32, mut y : i32, msg : &str){
     }
 
     console.execute(MoveTo(x as u16, y as u16)).expect("MoveTo failed");
-    console.write(msg.as_ref()).unwrap();
+    console.write_all(msg.as_ref()).unwrap();
 }
 
 pub fn clear_console(){
@@ -95,4 +137,4 @@ pub fn clear_console(){
         console,
         terminal::Clear(terminal::ClearType::All)
     ).expect("Terminal::Clear failed");
-}
\ No newline at end of file
+}
\ No newline at end of file